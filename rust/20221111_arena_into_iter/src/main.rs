@@ -1,29 +1,48 @@
-use std::{rc::{Weak, Rc}, cell::RefCell, fmt::Display};
+use std::{rc::{Weak, Rc}, cell::RefCell, fmt::Display, marker::PhantomData};
 
 type RispExpRef = Option<Weak<RefCell<Cell>>>;
 type RispExpRefStrong = Option<Rc<RefCell<Cell>>>;
+type AtomRef = Weak<RispAtom>;
 
-struct Arena(Vec<RispExpRefStrong>);
+/// Owns every cons cell and, out-of-line, every atom. Cells only hold weak
+/// references, so everything lives exactly as long as the arena.
+struct Arena {
+    cells: Vec<RispExpRefStrong>,
+    atoms: Vec<Rc<RispAtom>>,
+}
 
 impl Arena {
-    fn new() {
-        Arena(Vec::with_capacity(1000));
+    fn new() -> Self {
+        Arena {
+            cells: Vec::with_capacity(1000),
+            atoms: Vec::with_capacity(1000),
+        }
     }
 
     fn alloc(&mut self, cell: Cell) -> RispExpRef {
         let rc = Rc::new(RefCell::new(cell));
-        self.0.push(Some(rc.clone()));
+        self.cells.push(Some(rc.clone()));
         Some(Rc::downgrade(&rc))
     }
+
+    fn alloc_atom(&mut self, atom: RispAtom) -> AtomRef {
+        let rc = Rc::new(atom);
+        self.atoms.push(rc.clone());
+        Rc::downgrade(&rc)
+    }
 }
 
+/// A list; `None` is the empty list.
 struct RispExp(RispExpRef);
 
+/// A cons cell: the car points at an atom, the cdr at the rest of the list,
+/// or `None` at the end.
 struct Cell {
-    car: RispExpRef,
+    car: AtomRef,
     cdr: RispExpRef,
 }
 
+#[derive(Debug, PartialEq)]
 enum RispAtom {
     Int(i64),
     Symbol(String),
@@ -50,56 +69,266 @@ impl From<&str> for RispAtom {
     }
 }
 
-impl From<i64> for Cell {
-    
-}
-
 impl Display for Cell {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Cell { car: Some(car), cdr: Some(cdr) } => {
-                let car_ptr = car.upgrade().unwrap();
-                let cdr_ptr = cdr.upgrade().unwrap();
-                let car_ref = car_ptr.borrow();
-                let cdr_ref = cdr_ptr.borrow();
-                write!(f, "({} . {})", car_ref, cdr_ref)
-            },
-            Cell { car: Some(car), cdr: None } => {
-                let car_ptr = car.upgrade().unwrap();
-                let car_ref = car_ptr.borrow();
-                write!(f, "{}", car_ref)
-            },
-            Cell { car: None, cdr: Some(..) } => {
-                unreachable!("cdr without car");
-            },
-            Cell { car: None, cdr: None } => write!(f, "nil"),
-        }
+        write!(f, "{}", self.car.upgrade().unwrap())
     }
 }
 
 impl Display for RispExp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.0 {
-            Some(exp) => {
-                let exp_ptr = exp.upgrade().unwrap();
-                let exp_ref = exp_ptr.borrow();
-                write!(f, "{}", exp_ref)
-            },
-            None => write!(f, "nil"),
+        if self.0.is_none() {
+            return write!(f, "nil");
         }
+        write!(f, "(")?;
+        for (i, atom) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", atom)?;
+        }
+        write!(f, ")")
     }
 }
 
+fn upgrade(exp: &Weak<RefCell<Cell>>) -> Rc<RefCell<Cell>> {
+    exp.upgrade().expect("cell outlived its arena")
+}
+
 impl RispExp {
     fn new() -> Self {
         RispExp(None)
     }
 
-    fn cons(&mut self, car: RispExpRef) {
+    /// Pushes `car` onto the front of the list.
+    fn cons(&mut self, arena: &mut Arena, car: AtomRef) {
+        let cdr = self.0.take();
+        self.0 = arena.alloc(Cell { car, cdr });
+    }
+
+    fn pop(&mut self) -> Option<Rc<RispAtom>> {
+        self.0.take().map(|cell| {
+            let cell = upgrade(&cell);
+            let cell = cell.borrow();
+            self.0 = cell.cdr.clone();
+            cell.car.upgrade().unwrap()
+        })
+    }
+
+    fn peek(&self) -> Option<Rc<RispAtom>> {
+        self.0.as_ref().map(|cell| upgrade(cell).borrow().car.upgrade().unwrap())
+    }
+}
+
+// IntoIter
+struct IntoIter(RispExp);
+
+impl Iterator for IntoIter {
+    type Item = Rc<RispAtom>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+}
+
+impl IntoIterator for RispExp {
+    type Item = Rc<RispAtom>;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_iter()
+    }
+}
+
+impl RispExp {
+    fn into_iter(self) -> IntoIter {
+        IntoIter(self)
+    }
+}
+
+// Iter
+struct Iter<'a>(RispExpRef, PhantomData<&'a RispExp>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Rc<RispAtom>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.take().map(|cell| {
+            let cell = upgrade(&cell);
+            let cell = cell.borrow();
+            self.0 = cell.cdr.clone();
+            cell.car.upgrade().unwrap()
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a RispExp {
+    type Item = Rc<RispAtom>;
+    type IntoIter = Iter<'a>;
 
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl RispExp {
+    fn iter(&self) -> Iter<'_> {
+        Iter(self.0.clone(), PhantomData)
+    }
+}
+
+// IterMut: yields the cells themselves so the caller can repoint their car
+struct IterMut<'a>(RispExpRef, PhantomData<&'a mut RispExp>);
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = Rc<RefCell<Cell>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.take().map(|cell| {
+            let cell = upgrade(&cell);
+            self.0 = cell.borrow().cdr.clone();
+            cell
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a mut RispExp {
+    type Item = Rc<RefCell<Cell>>;
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl RispExp {
+    fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut(self.0.clone(), PhantomData)
     }
 }
 
 fn main() {
-    println!("Hello, world!");
+    let mut arena = Arena::new();
+    let mut lst = RispExp::new();
+    for atom in [RispAtom::from(3), "b".into(), "a".into()] {
+        let atom = arena.alloc_atom(atom);
+        lst.cons(&mut arena, atom);
+    }
+    println!("{}", lst);
+    if let Some(head) = lst.peek() {
+        println!("head: {}", head);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new(arena: &mut Arena) -> RispExp {
+        let mut lst = RispExp::new();
+        for name in ["a", "b", "c"] {
+            let atom = arena.alloc_atom(name.into());
+            lst.cons(arena, atom);
+        }
+        lst
+    }
+
+    fn sym(name: &str) -> Option<Rc<RispAtom>> {
+        Some(Rc::new(name.into()))
+    }
+
+    #[test]
+    fn test_basics() {
+        let mut arena = Arena::new();
+        let mut lst = RispExp::new();
+        assert_eq!(lst.pop(), None);
+        assert_eq!(lst.to_string(), "nil");
+
+        let one = arena.alloc_atom(1.into());
+        let two = arena.alloc_atom("two".into());
+        lst.cons(&mut arena, one.clone());
+        lst.cons(&mut arena, two);
+        lst.cons(&mut arena, one);
+        assert_eq!(lst.to_string(), "(1 two 1)");
+
+        assert_eq!(lst.pop(), Some(Rc::new(1.into())));
+        assert_eq!(lst.pop(), sym("two"));
+        assert_eq!(lst.pop(), Some(Rc::new(1.into())));
+        assert_eq!(lst.pop(), None);
+
+        // cells and atoms are both arena-owned
+        assert_eq!(arena.cells.len(), 3);
+        assert_eq!(arena.atoms.len(), 2);
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut arena = Arena::new();
+        assert_eq!(RispExp::new().peek(), None);
+
+        let mut lst = new(&mut arena);
+        assert_eq!(lst.peek(), sym("c"));
+        assert_eq!(lst.pop(), sym("c"));
+        assert_eq!(lst.peek(), sym("b"));
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut arena = Arena::new();
+
+        // direct iterator usage
+        let lst = new(&mut arena);
+        let mut iter = lst.into_iter();
+        assert_eq!(iter.next(), sym("c"));
+        assert_eq!(iter.next(), sym("b"));
+        assert_eq!(iter.next(), sym("a"));
+        assert_eq!(iter.next(), None);
+
+        // for-in usage (implicit)
+        let lst = new(&mut arena);
+        let mut expected = vec!["a", "b", "c"];
+        for val in lst {
+            assert_eq!(val.to_string(), expected.pop().unwrap());
+        }
+        assert!(expected.is_empty());
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut arena = Arena::new();
+
+        // direct iterator usage
+        let lst = new(&mut arena);
+        let mut iter = lst.iter();
+        assert_eq!(iter.next(), sym("c"));
+        assert_eq!(iter.next(), sym("b"));
+        assert_eq!(iter.next(), sym("a"));
+        assert_eq!(iter.next(), None);
+
+        // for-in usage (implicit), leaving the list intact
+        let mut expected = vec!["a", "b", "c"];
+        for val in &lst {
+            assert_eq!(val.to_string(), expected.pop().unwrap());
+        }
+        assert_eq!(lst.to_string(), "(c b a)");
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut arena = Arena::new();
+        let x = arena.alloc_atom("x".into());
+
+        let mut lst = new(&mut arena);
+        assert_eq!(lst.to_string(), "(c b a)");
+
+        let mut expected = vec!["a", "b", "c"];
+        for cell in &mut lst {
+            assert_eq!(cell.borrow().to_string(), expected.pop().unwrap());
+            if cell.borrow().to_string() == "b" {
+                cell.borrow_mut().car = x.clone();
+            }
+        }
+
+        assert_eq!(lst.to_string(), "(c x a)");
+    }
 }
@@ -0,0 +1,204 @@
+//! The `risp!` macro: S-expression syntax in Rust code, allocated in an
+//! arena. See `risp::risp!` for the accepted syntax.
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+type Error = (String, Span);
+
+/// `risp!(arena, datum)`: allocates `datum` in `arena` and evaluates to its
+/// `RispExpRef`.
+#[proc_macro]
+pub fn risp(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(code) => code.parse().expect("generated code parses"),
+        Err((msg, span)) => compile_error(&msg, span),
+    }
+}
+
+fn expand(input: TokenStream) -> Result<String, Error> {
+    let mut tokens = input.into_iter();
+    let mut arena = TokenStream::new();
+    for token in tokens.by_ref() {
+        match token {
+            TokenTree::Punct(p) if p.as_char() == ',' => break,
+            token => arena.extend([token]),
+        }
+    }
+    if arena.is_empty() {
+        return Err(("expected `risp!(arena, datum)`".into(), Span::call_site()));
+    }
+
+    let mut parser = Parser { tokens: tokens.collect(), pos: 0 };
+    let code = parser.datum(Span::call_site())?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        return Err(("expected a single datum".into(), token.span()));
+    }
+    // the arena expression is evaluated once, whether it names an `Arena`
+    // or a `&mut Arena`
+    Ok(format!("{{ let {}: &mut ::risp::Arena = ({}).risp_arena(); {} }}", ARENA, arena, code))
+}
+
+/// The variable the generated code holds the arena in.
+const ARENA: &str = "__risp_arena";
+
+struct Parser {
+    tokens: Vec<TokenTree>,
+    pos: usize,
+}
+
+impl Parser {
+    /// Generates the code allocating the next datum.
+    fn datum(&mut self, end: Span) -> Result<String, Error> {
+        let arena = ARENA;
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return Err(("expected a datum".into(), end));
+        };
+        match token {
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Parenthesis => {
+                self.pos += 1;
+                let mut inner = Parser { tokens: g.stream().into_iter().collect(), pos: 0 };
+                inner.list(g.span_close())
+            },
+            TokenTree::Group(g) => Err(("lists are written with `( )`".into(), g.span())),
+            TokenTree::Punct(p) if p.as_char() == ',' => {
+                self.pos += 1;
+                let value = self.tokens.get(self.pos).ok_or(("expected a Rust value after `,`".into(), p.span()))?;
+                self.pos += 1;
+                Ok(format!("{}.interpolate({})", arena, value))
+            },
+            // `'a` reaches us as the lifetime `'a`
+            TokenTree::Punct(p) if p.as_char() == '\'' => {
+                self.pos += 1;
+                let quoted = self.datum(p.span())?;
+                Ok(format!("{{ let __risp_items = [{}.symbol(\"quote\"), {}]; {}.list(__risp_items) }}", arena, quoted, arena))
+            },
+            TokenTree::Literal(lit) if is_string(&lit) => {
+                self.pos += 1;
+                Ok(format!("{}.alloc(::risp::RispExp::Atom(::risp::RispAtom::Str(::std::string::String::from({}))))", arena, lit))
+            },
+            TokenTree::Literal(lit) if lit.to_string().starts_with('\'') => {
+                self.pos += 1;
+                Ok(format!("{}.alloc(::risp::RispExp::from({}))", arena, lit))
+            },
+            _ => {
+                let (text, len) = self.run();
+                if text == "." {
+                    return Err(("`.` is only allowed before the last element of a list".into(), token.span()));
+                }
+                self.pos += len;
+                check_atom(&text).map_err(|msg| (msg, token.span()))?;
+                Ok(atom(arena, &text))
+            },
+        }
+    }
+
+    /// Generates a list from the tokens inside its parentheses.
+    fn list(&mut self, close: Span) -> Result<String, Error> {
+        let mut items = Vec::new();
+        let mut tail = format!("{}.nil()", ARENA);
+        while let Some(token) = self.tokens.get(self.pos) {
+            if self.run().0 == "." {
+                if items.is_empty() {
+                    return Err(("expected a datum before `.`".into(), token.span()));
+                }
+                self.pos += 1;
+                tail = self.datum(close)?;
+                if let Some(token) = self.tokens.get(self.pos) {
+                    return Err(("expected `)` after the tail of a dotted list".into(), token.span()));
+                }
+                break;
+            }
+            items.push(self.datum(close)?);
+        }
+        if items.is_empty() {
+            return Ok(tail);
+        }
+        Ok(format!(
+            "{{ let __risp_items = [{}]; let __risp_tail = {}; \
+             __risp_items.into_iter().rev().fold(__risp_tail, |cdr, car| {}.alloc((car, cdr).into())) }}",
+            items.join(", "), tail, ARENA,
+        ))
+    }
+
+    /// The text of the atom starting at the current token and how many
+    /// tokens it spans. Rust splits `eqv?`, `set-car!` or `-1` into several
+    /// tokens; the ones written without space between them are joined.
+    fn run(&self) -> (String, usize) {
+        let mut text = String::new();
+        let mut len = 0;
+        let mut prev: Option<&TokenTree> = None;
+        for token in &self.tokens[self.pos..] {
+            let joins = match token {
+                TokenTree::Ident(_) => true,
+                TokenTree::Punct(p) => !matches!(p.as_char(), ',' | '\''),
+                TokenTree::Literal(lit) => !is_string(lit) && !lit.to_string().starts_with('\''),
+                TokenTree::Group(_) => false,
+            };
+            if !joins || prev.is_some_and(|prev| !adjacent(prev.span(), token.span())) {
+                break;
+            }
+            text.push_str(&token.to_string());
+            len += 1;
+            prev = Some(token);
+        }
+        (text, len)
+    }
+}
+
+/// Numbers, booleans and symbols are parsed at run time by the reader, so
+/// both spell them the same way.
+fn atom(arena: &str, text: &str) -> String {
+    format!("{}.read_atom({:?})", arena, text)
+}
+
+/// Rejects the atoms the reader would fail on, which `read_atom` would
+/// otherwise only report by panicking at run time: a ratio with a zero
+/// denominator.
+fn check_atom(text: &str) -> Result<(), String> {
+    let Some((numer, denom)) = text.split_once('/') else { return Ok(()) };
+    let digits = numer.strip_prefix(['+', '-']).unwrap_or(numer);
+    let is_ratio = !digits.is_empty()
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && !denom.is_empty()
+        && denom.chars().all(|c| c.is_ascii_digit());
+    if is_ratio && denom.chars().all(|c| c == '0') {
+        return Err(format!("invalid number `{}`: zero denominator", text));
+    }
+    Ok(())
+}
+
+fn is_string(lit: &Literal) -> bool {
+    let text = lit.to_string();
+    text.starts_with('"') || text.starts_with("r\"") || text.starts_with("r#")
+}
+
+fn adjacent(a: Span, b: Span) -> bool {
+    let (end, start) = (a.end(), b.start());
+    end.line() == start.line() && end.column() == start.column()
+}
+
+fn compile_error(msg: &str, span: Span) -> TokenStream {
+    let mut lit = Literal::string(msg);
+    lit.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut args = Group::new(Delimiter::Parenthesis, TokenTree::Literal(lit).into());
+    args.set_span(span);
+    [TokenTree::Ident(Ident::new("compile_error", span)), bang.into(), args.into()].into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_atom() {
+        assert!(check_atom("1/2").is_ok());
+        assert!(check_atom("-3/06").is_ok());
+        assert!(check_atom("a/0").is_ok());
+        assert!(check_atom("1/0x").is_ok());
+        assert!(check_atom("x").is_ok());
+        assert_eq!(check_atom("1/0").unwrap_err(), "invalid number `1/0`: zero denominator");
+        assert!(check_atom("-5/000").is_err());
+    }
+}
@@ -0,0 +1,6 @@
+fn main() {
+    let mut arena = risp::Arena::new();
+    risp::risp!(arena, (. a));
+    risp::risp!(arena, (a . b c));
+    risp::risp!(arena, .);
+}
@@ -0,0 +1,7 @@
+fn main() {
+    let mut arena = risp::Arena::new();
+    risp::risp!(arena, [a b]);
+    risp::risp!(arena, a b);
+    risp::risp!(arena, (a ,));
+    risp::risp!(arena);
+}
@@ -0,0 +1,4 @@
+fn main() {
+    let mut arena = risp::Arena::new();
+    risp::risp!(arena, (1/2 3/0));
+}
@@ -0,0 +1,16 @@
+use risp::{risp, upgrade, Arena};
+
+fn show(exp: &risp::RispExpRef) -> String {
+    upgrade(exp).unwrap().borrow().to_string()
+}
+
+fn main() {
+    let mut arena = Arena::new();
+    assert_eq!(show(&risp!(arena, (define (f x) (if (<= x 1/2) 'small "big")))), "(define (f x) (if (<= x 1/2) (quote small) \"big\"))");
+    assert_eq!(show(&risp!(arena, (a . (b . c)))), "(a b . c)");
+
+    let by_ref = &mut arena;
+    let n = 2;
+    assert_eq!(show(&risp!(by_ref, (+ 1 ,n))), "(+ 1 2)");
+    assert_eq!(show(&risp!(&mut arena, 'a')), "#\\a");
+}
@@ -0,0 +1,432 @@
+use std::{rc::{Rc, Weak}, cell::RefCell, collections::{HashMap, HashSet}, fmt::Display};
+
+use crate::{exp::{RispAtom, RispExp}, reader, symbol::{Interner, Keywords, SymbolId}};
+
+pub type RispExpRef = Weak<RefCell<RispExp>>;
+pub type RispExpRefStrong = Rc<RefCell<RispExp>>;
+
+/// Returned when a handle outlives the cell it points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DanglingRef;
+
+impl Display for DanglingRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "dangling reference to a freed cell")
+    }
+}
+
+impl std::error::Error for DanglingRef {}
+
+pub fn upgrade(exp: &RispExpRef) -> Result<RispExpRefStrong, DanglingRef> {
+    exp.upgrade().ok_or(DanglingRef)
+}
+
+/// Owns every cell. Symbols are interned: each name has one [`SymbolId`]
+/// and one canonical cell, which lives as long as the arena and is never
+/// collected. So are nil, `#t` and `#f`, which `eq?` can then compare by
+/// identity.
+pub struct Arena {
+    cells: Vec<RispExpRefStrong>,
+    interner: Interner,
+    symbols: HashMap<SymbolId, RispExpRefStrong>,
+    nil: RispExpRefStrong,
+    bools: [RispExpRefStrong; 2],
+    kw: Rc<Keywords>,
+    /// How many calls of `eval` are running; only the outermost collects.
+    pub(crate) eval_depth: usize,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        let mut interner = Interner::default();
+        let kw = Rc::new(Keywords::new(&mut interner));
+        Arena {
+            cells: Vec::with_capacity(100),
+            interner,
+            symbols: HashMap::new(),
+            nil: Rc::new(RefCell::new(RispExp::Nil)),
+            bools: [false, true].map(|b| Rc::new(RefCell::new(b.into()))),
+            kw,
+            eval_depth: 0,
+        }
+    }
+
+    /// Stores `exp` in a new cell, except for booleans, which get their
+    /// canonical cell.
+    pub fn alloc(&mut self, exp: RispExp) -> RispExpRef {
+        if let RispExp::Atom(RispAtom::Bool(b)) = exp {
+            return self.boolean(b);
+        }
+        let rc = Rc::new(RefCell::new(exp));
+        self.cells.push(rc.clone());
+        Rc::downgrade(&rc)
+    }
+
+    pub fn intern(&mut self, name: &str) -> SymbolId {
+        self.interner.intern(name)
+    }
+
+    /// The id for `name`, if any cell or builtin has used it.
+    pub fn lookup(&self, name: &str) -> Option<SymbolId> {
+        self.interner.lookup(name)
+    }
+
+    /// The interned keywords, shared so a caller can hold them while it
+    /// allocates.
+    pub fn kw(&self) -> Rc<Keywords> {
+        self.kw.clone()
+    }
+
+    /// The canonical cell holding symbol `id`, or nil for the name `nil`.
+    pub fn symbol_cell(&mut self, id: &SymbolId) -> RispExpRef {
+        if *id.base() == self.kw.nil {
+            return self.nil();
+        }
+        let cell = self.symbols
+            .entry(id.clone())
+            .or_insert_with(|| Rc::new(RefCell::new(RispExp::Atom(RispAtom::Symbol(id.clone())))));
+        Rc::downgrade(cell)
+    }
+
+    /// The canonical cell for the symbol called `name`.
+    pub fn symbol(&mut self, name: &str) -> RispExpRef {
+        let id = self.intern(name);
+        self.symbol_cell(&id)
+    }
+
+    /// The canonical empty list.
+    pub fn nil(&self) -> RispExpRef {
+        Rc::downgrade(&self.nil)
+    }
+
+    /// The canonical `#t` or `#f` cell.
+    pub fn boolean(&self, b: bool) -> RispExpRef {
+        Rc::downgrade(&self.bools[b as usize])
+    }
+
+    /// Allocates a proper list holding `items`.
+    pub fn list(&mut self, items: impl IntoIterator<Item = RispExpRef>) -> RispExpRef {
+        let items: Vec<_> = items.into_iter().collect();
+        let nil = self.nil();
+        items.into_iter().rev().fold(nil, |cdr, car| self.alloc((car, cdr).into()))
+    }
+
+    /// Allocates each of `items` and a proper list holding them, as in
+    /// `arena.list_of([1, 2, 3])`.
+    pub fn list_of<T: Into<RispExp>>(&mut self, items: impl IntoIterator<Item = T>) -> RispExpRef {
+        let items: Vec<_> = items.into_iter().map(|item| self.alloc(item.into())).collect();
+        self.list(items)
+    }
+
+    /// Allocates the atom spelled `text` as the reader would, for `risp!`.
+    ///
+    /// # Panics
+    /// If `text` is not a valid atom, such as the number `1/0`.
+    pub fn read_atom(&mut self, text: &str) -> RispExpRef {
+        reader::read_atom(self, text).unwrap_or_else(|e| panic!("risp!: {}", e))
+    }
+
+    /// `self`, so that `risp!` can take the arena as an `Arena` or a
+    /// `&mut Arena` alike.
+    #[doc(hidden)]
+    pub fn risp_arena(&mut self) -> &mut Arena {
+        self
+    }
+
+    /// Allocates a Rust value spliced into a `risp!` form with `,expr`.
+    pub fn interpolate(&mut self, val: impl Interpolate) -> RispExpRef {
+        val.into_exp(self)
+    }
+
+    /// Number of collectable cells, not counting interned symbols.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Mark-and-sweep: drops every cell not reachable from `roots` through
+    /// car/cdr links or closures and returns how many cells were reclaimed.
+    pub fn collect<'a>(&mut self, roots: impl IntoIterator<Item = &'a RispExpRef>) -> usize {
+        let marked = self.mark(roots.into_iter().cloned().collect());
+        let before = self.cells.len();
+        self.cells.retain(|rc| marked.contains(&Rc::as_ptr(rc)));
+        before - self.cells.len()
+    }
+
+    /// Like `collect`, but only sweeps the cells allocated after the first
+    /// `start`, which are kept and count as roots.
+    pub fn collect_since<'a>(&mut self, start: usize, roots: impl IntoIterator<Item = &'a RispExpRef>) -> usize {
+        let old = self.cells[..start].iter().map(Rc::downgrade);
+        let marked = self.mark(old.chain(roots.into_iter().cloned()).collect());
+        let young = self.cells.split_off(start);
+        let before = young.len();
+        self.cells.extend(young.into_iter().filter(|rc| marked.contains(&Rc::as_ptr(rc))));
+        before - (self.cells.len() - start)
+    }
+
+    /// The cells reachable from `stack`.
+    fn mark(&self, mut stack: Vec<RispExpRef>) -> HashSet<*const RefCell<RispExp>> {
+        let mut marked = HashSet::new();
+        let mut marked_envs = HashSet::new();
+        while let Some(exp) = stack.pop() {
+            if !marked.insert(exp.as_ptr()) {
+                continue;
+            }
+            let Some(exp) = exp.upgrade() else { continue };
+            let exp = exp.borrow();
+            match &*exp {
+                RispExp::Cons{car, cdr} => {
+                    stack.push(car.clone());
+                    stack.push(cdr.clone());
+                },
+                RispExp::Lambda(lambda) | RispExp::Macro(lambda) => {
+                    stack.push(lambda.params.clone());
+                    stack.push(lambda.body.clone());
+                    lambda.env.trace(&mut marked_envs, &mut stack);
+                },
+                RispExp::Syntax(rules) => {
+                    stack.push(rules.rules.clone());
+                    rules.env.trace(&mut marked_envs, &mut stack);
+                },
+                RispExp::Closure(closure) => {
+                    stack.extend([&closure.code, &closure.env].map(Clone::clone));
+                },
+                RispExp::Continuation(k) => {
+                    stack.extend([&k.s, &k.e, &k.c, &k.d, &k.winders].map(Clone::clone));
+                },
+                RispExp::Atom(RispAtom::Symbol(s)) => {
+                    // a renamed symbol keeps its macro's environment alive
+                    let mut s = s;
+                    while let Some((original, env)) = s.renamed() {
+                        if let Some(env) = env {
+                            env.trace(&mut marked_envs, &mut stack);
+                        }
+                        s = original;
+                    }
+                },
+                RispExp::Nil | RispExp::Atom(_) | RispExp::Builtin(_) => (),
+            }
+        }
+        marked
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[macro_export]
+macro_rules! alloc {
+    ($arena: ident, [$exp: tt]) => {{
+        let e = $crate::alloc!($arena, $exp);
+        let nil = $arena.nil();
+        $arena.alloc((e, nil).into())
+    }};
+    ($arena: ident, [$car: tt, $cdr: tt]) => {{
+        let car = $crate::alloc!($arena, $car);
+        let cdr = $crate::alloc!($arena, $cdr);
+        $arena.alloc((car, cdr).into())
+    }};
+    ($arena: ident, [$car: tt, $($rest: tt),*]) => {{
+        let car = $crate::alloc!($arena, $car);
+        let cdr = $crate::alloc!($arena, [$($rest),*]);
+        $arena.alloc((car, cdr).into())
+    }};
+    ($arena: ident, $exp: tt) => {
+        $exp.clone()
+    };
+}
+
+/// Values `risp!` can splice in with `,expr`. Handles are used as they are;
+/// everything else becomes a new cell, and symbols their canonical one.
+pub trait Interpolate {
+    fn into_exp(self, arena: &mut Arena) -> RispExpRef;
+}
+
+impl Interpolate for RispExpRef {
+    fn into_exp(self, _arena: &mut Arena) -> RispExpRef {
+        self
+    }
+}
+
+impl Interpolate for &RispExpRef {
+    fn into_exp(self, _arena: &mut Arena) -> RispExpRef {
+        self.clone()
+    }
+}
+
+impl Interpolate for SymbolId {
+    fn into_exp(self, arena: &mut Arena) -> RispExpRef {
+        arena.symbol_cell(&self)
+    }
+}
+
+impl Interpolate for RispAtom {
+    fn into_exp(self, arena: &mut Arena) -> RispExpRef {
+        match self {
+            RispAtom::Symbol(id) => arena.symbol_cell(&id),
+            atom => arena.alloc(RispExp::Atom(atom)),
+        }
+    }
+}
+
+impl Interpolate for &str {
+    fn into_exp(self, arena: &mut Arena) -> RispExpRef {
+        arena.alloc(RispExp::Atom(RispAtom::Str(self.to_string())))
+    }
+}
+
+impl Interpolate for String {
+    fn into_exp(self, arena: &mut Arena) -> RispExpRef {
+        arena.alloc(RispExp::Atom(RispAtom::Str(self)))
+    }
+}
+
+macro_rules! interpolate_atom {
+    ($($t: ty),*) => {$(
+        impl Interpolate for $t {
+            fn into_exp(self, arena: &mut Arena) -> RispExpRef {
+                arena.alloc(self.into())
+            }
+        }
+    )*};
+}
+
+interpolate_atom!(i64, f64, bool, char);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect() {
+        let mut arena = Arena::new();
+        let nil = arena.nil();
+        let v1 = arena.alloc(1.into());
+        let v2 = arena.alloc(2.into());
+        let v3 = arena.alloc(3.into());
+        let lst = alloc!(arena, [v1, v2, nil]);
+        let garbage = alloc!(arena, [v3, v3]);
+        assert_eq!(arena.len(), 6);
+
+        assert_eq!(arena.collect([&lst]), 2);
+        assert_eq!(arena.len(), 4);
+        assert_eq!(lst.upgrade().unwrap().borrow().to_string(), "(1 2)");
+        assert!(v1.upgrade().is_some());
+        assert!(v3.upgrade().is_none());
+        assert!(garbage.upgrade().is_none());
+
+        // nothing left to reclaim
+        assert_eq!(arena.collect([&lst]), 0);
+
+        assert_eq!(arena.collect([]), 4);
+        assert!(arena.is_empty());
+        assert!(lst.upgrade().is_none());
+        assert!(nil.upgrade().is_some());
+    }
+
+    #[test]
+    fn test_list() {
+        let mut arena = Arena::new();
+        let lst = arena.list_of([1, 2, 3]);
+        assert_eq!(lst.upgrade().unwrap().borrow().to_string(), "(1 2 3)");
+        let lst = arena.list_of("ab".chars());
+        assert_eq!(lst.upgrade().unwrap().borrow().to_string(), r"(#\a #\b)");
+
+        let names = ["x", "y"].map(|name| arena.symbol(name));
+        let lst = arena.list(names.into_iter().rev());
+        assert_eq!(lst.upgrade().unwrap().borrow().to_string(), "(y x)");
+        assert!(arena.list([]).ptr_eq(&arena.nil()));
+    }
+
+    #[test]
+    fn test_symbols() {
+        let mut arena = Arena::new();
+        let nil = arena.nil();
+        assert!(nil.ptr_eq(&arena.nil()));
+        assert!(nil.ptr_eq(&arena.symbol("nil")));
+        assert!(nil.upgrade().unwrap().borrow().is_nil());
+        assert!(arena.symbol("quote").ptr_eq(&arena.symbol("quote")));
+        assert!(!arena.symbol("quote").ptr_eq(&arena.symbol("lambda")));
+        assert!(arena.alloc(true.into()).ptr_eq(&arena.boolean(true)));
+        assert!(!arena.boolean(true).ptr_eq(&arena.boolean(false)));
+        assert!(arena.is_empty());
+
+        let id = arena.intern("foo");
+        assert_eq!(arena.lookup("foo"), Some(id.clone()));
+        assert_eq!(arena.lookup("bar"), None);
+        let foo = arena.symbol_cell(&id);
+        assert_eq!(foo.upgrade().unwrap().borrow().to_string(), "foo");
+
+        // the alloc! macro shares the one nil cell
+        let v1 = arena.alloc(1.into());
+        let lst = alloc!(arena, [v1]);
+        assert!(lst.upgrade().unwrap().borrow().cdr_weak().unwrap().ptr_eq(&nil));
+    }
+
+    #[test]
+    fn test_collect_cycle() {
+        let mut arena = Arena::new();
+        let v1 = arena.alloc(1.into());
+        let a = arena.alloc((&v1, &v1).into());
+        let b = arena.alloc((&v1, &a).into());
+        a.upgrade().unwrap().borrow_mut().set_cdr(b.clone()).unwrap();
+
+        assert_eq!(arena.collect([&b]), 0);
+        assert_eq!(b.upgrade().unwrap().borrow().to_string(), "#0=(1 1 . #0#)");
+
+        // unreachable cycles are reclaimed too
+        assert_eq!(arena.collect([&v1]), 2);
+        assert!(a.upgrade().is_none());
+        assert!(b.upgrade().is_none());
+        assert!(v1.upgrade().is_some());
+    }
+
+    #[test]
+    fn test_risp_macro() {
+        let mut arena = Arena::new();
+        let exp = crate::risp!(arena, (ldc (quote a) 1 "s"));
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "(ldc (quote a) 1 \"s\")");
+        assert_eq!(crate::risp!(arena, ()).as_ptr(), arena.nil().as_ptr());
+
+        // symbols are Lisp-style names, and shared with the reader
+        let exp = crate::risp!(arena, (set-car! eqv? ... -1 2.5 #t #f x_1 'c'));
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "(set-car! eqv? ... -1 2.5 #t #f x_1 #\\c)");
+        let first = exp.upgrade().unwrap().borrow().car_weak().unwrap();
+        assert!(first.ptr_eq(&arena.symbol("set-car!")));
+
+        // numbers read exactly as the reader reads them
+        let exp = crate::risp!(arena, (99999999999999999999 1/2 -3/6 2.5e-3 1e44 +inf.0 inf));
+        let read = reader::read_one(&mut arena, "(99999999999999999999 1/2 -3/6 2.5e-3 1e44 +inf.0 inf)").unwrap();
+        assert!(crate::equal::equal(&exp, &read).unwrap());
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "(99999999999999999999 1/2 -1/2 0.0025 1.0e44 +inf.0 inf)");
+
+        let exp = crate::risp!(arena, ((a . 1) (b 2 . (3)) . c));
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "((a . 1) (b 2 3) . c)");
+
+        // ,expr splices in handles as they are and allocates other values
+        let v = arena.alloc(7.into());
+        let (n, rest) = (40i64, arena.intern("rest"));
+        let exp = crate::risp!(arena, (+ ,(&v) ,(n + 2) ,"t" . ,rest));
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "(+ 7 42 \"t\" . rest)");
+        let second = exp.upgrade().unwrap().borrow().cdr_weak().unwrap();
+        assert!(second.upgrade().unwrap().borrow().car_weak().unwrap().ptr_eq(&v));
+
+        // 'sym quotes a symbol, as in Lisp; Rust does not lex '( or '1
+        let exp = crate::risp!(arena, ('a (b 'c)));
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "((quote a) (b (quote c)))");
+
+        // the arena may be given by reference, and is evaluated once
+        let exp = crate::risp!(&mut arena, (1 2));
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "(1 2)");
+        let mut evaluated = 0;
+        let exp = crate::risp!({ evaluated += 1; &mut arena }, (1 (2 3) . 4));
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "(1 (2 3) . 4)");
+        assert_eq!(evaluated, 1);
+    }
+}
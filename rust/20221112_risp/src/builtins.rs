@@ -0,0 +1,276 @@
+use std::cmp::Ordering;
+
+use crate::{
+    arena::{upgrade, Arena, RispExpRef},
+    error::{Result, RispError},
+    equal,
+    exp::{Builtin, RispAtom, RispExp},
+    num::{self, Op},
+};
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "+", f: add },
+    Builtin { name: "-", f: sub },
+    Builtin { name: "*", f: mul },
+    Builtin { name: "/", f: div },
+    Builtin { name: "=", f: num_eq },
+    Builtin { name: "<", f: lt },
+    Builtin { name: ">", f: gt },
+    Builtin { name: "<=", f: le },
+    Builtin { name: ">=", f: ge },
+    Builtin { name: "exact?", f: is_exact },
+    Builtin { name: "inexact?", f: is_inexact },
+    Builtin { name: "exact->inexact", f: exact_to_inexact },
+    Builtin { name: "inexact->exact", f: inexact_to_exact },
+    Builtin { name: "car", f: car },
+    Builtin { name: "cdr", f: cdr },
+    Builtin { name: "cons", f: cons },
+    Builtin { name: "list", f: list },
+    Builtin { name: "set-car!", f: set_car },
+    Builtin { name: "set-cdr!", f: set_cdr },
+    Builtin { name: "atom?", f: is_atom },
+    Builtin { name: "null?", f: is_null },
+    Builtin { name: "nil?", f: is_nil },
+    Builtin { name: "eq?", f: is_eq },
+    Builtin { name: "eqv?", f: is_eqv },
+    Builtin { name: "equal?", f: is_equal },
+    Builtin { name: "string-length", f: string_length },
+    Builtin { name: "substring", f: substring },
+    Builtin { name: "string-append", f: string_append },
+    Builtin { name: "string->symbol", f: string_to_symbol },
+    Builtin { name: "symbol->string", f: symbol_to_string },
+];
+
+pub(crate) fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+pub(crate) fn check_arity(name: &str, args: &[RispExpRef], min: usize, max: Option<usize>) -> Result<()> {
+    if args.len() < min || max.is_some_and(|max| args.len() > max) {
+        return Err(RispError::arity(name, min, max, args.len()));
+    }
+    Ok(())
+}
+
+fn number_arg(arg: &RispExpRef) -> Result<RispAtom> {
+    match &*upgrade(arg)?.borrow() {
+        RispExp::Atom(n) if n.is_number() => Ok(n.clone()),
+        exp => Err(RispError::wrong_type("number", exp)),
+    }
+}
+
+fn string_arg(arg: &RispExpRef) -> Result<String> {
+    match &*upgrade(arg)?.borrow() {
+        RispExp::Atom(RispAtom::Str(s)) => Ok(s.clone()),
+        exp => Err(RispError::wrong_type("string", exp)),
+    }
+}
+
+fn index_arg(arg: &RispExpRef) -> Result<usize> {
+    match &*upgrade(arg)?.borrow() {
+        RispExp::Atom(RispAtom::Int(i)) if *i >= 0 => Ok(*i as usize),
+        exp => Err(RispError::wrong_type("index", exp)),
+    }
+}
+
+fn string_exp(arena: &mut Arena, s: String) -> RispExpRef {
+    arena.alloc(RispExp::Atom(RispAtom::Str(s)))
+}
+
+fn fold_numbers(arena: &mut Arena, init: RispAtom, args: &[RispExpRef], op: Op) -> Result<RispExpRef> {
+    let mut acc = init;
+    for arg in args {
+        acc = num::arith(op, &acc, &number_arg(arg)?)?;
+    }
+    Ok(arena.alloc(RispExp::Atom(acc)))
+}
+
+fn add(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    fold_numbers(arena, 0.into(), args, Op::Add)
+}
+
+fn sub(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("-", args, 1, None)?;
+    match args {
+        [x] => fold_numbers(arena, 0.into(), std::slice::from_ref(x), Op::Sub),
+        [first, rest @ ..] => fold_numbers(arena, number_arg(first)?, rest, Op::Sub),
+        [] => unreachable!(),
+    }
+}
+
+fn mul(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    fold_numbers(arena, 1.into(), args, Op::Mul)
+}
+
+fn div(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("/", args, 1, None)?;
+    match args {
+        [x] => fold_numbers(arena, 1.into(), std::slice::from_ref(x), Op::Div),
+        [first, rest @ ..] => fold_numbers(arena, number_arg(first)?, rest, Op::Div),
+        [] => unreachable!(),
+    }
+}
+
+fn compare(arena: &mut Arena, name: &str, args: &[RispExpRef], op: fn(Ordering) -> bool) -> Result<RispExpRef> {
+    check_arity(name, args, 1, None)?;
+    let nums = args.iter().map(number_arg).collect::<Result<Vec<_>>>()?;
+    let result = nums.windows(2).all(|w| num::compare(&w[0], &w[1]).is_some_and(op));
+    Ok(arena.boolean(result))
+}
+
+fn num_eq(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    compare(arena, "=", args, Ordering::is_eq)
+}
+
+fn lt(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    compare(arena, "<", args, Ordering::is_lt)
+}
+
+fn gt(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    compare(arena, ">", args, Ordering::is_gt)
+}
+
+fn le(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    compare(arena, "<=", args, Ordering::is_le)
+}
+
+fn ge(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    compare(arena, ">=", args, Ordering::is_ge)
+}
+
+fn is_exact(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("exact?", args, 1, Some(1))?;
+    let result = number_arg(&args[0])?.is_exact();
+    Ok(arena.boolean(result))
+}
+
+fn is_inexact(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("inexact?", args, 1, Some(1))?;
+    let result = !number_arg(&args[0])?.is_exact();
+    Ok(arena.boolean(result))
+}
+
+fn exact_to_inexact(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("exact->inexact", args, 1, Some(1))?;
+    let n = number_arg(&args[0])?.to_inexact();
+    Ok(arena.alloc(RispExp::Atom(n)))
+}
+
+fn inexact_to_exact(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("inexact->exact", args, 1, Some(1))?;
+    let n = number_arg(&args[0])?.to_exact()?;
+    Ok(arena.alloc(RispExp::Atom(n)))
+}
+
+/// `(car nil)` and `(cdr nil)` are nil, as in most Lisps.
+fn car(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("car", args, 1, Some(1))?;
+    match &*upgrade(&args[0])?.borrow() {
+        RispExp::Nil => Ok(arena.nil()),
+        exp => exp.car_weak(),
+    }
+}
+
+fn cdr(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("cdr", args, 1, Some(1))?;
+    match &*upgrade(&args[0])?.borrow() {
+        RispExp::Nil => Ok(arena.nil()),
+        exp => exp.cdr_weak(),
+    }
+}
+
+fn cons(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("cons", args, 2, Some(2))?;
+    Ok(arena.alloc((&args[0], &args[1]).into()))
+}
+
+/// `(set-car! pair val)`, returning the pair.
+fn set_car(_arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("set-car!", args, 2, Some(2))?;
+    upgrade(&args[0])?.borrow_mut().set_car(args[1].clone())?;
+    Ok(args[0].clone())
+}
+
+fn set_cdr(_arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("set-cdr!", args, 2, Some(2))?;
+    upgrade(&args[0])?.borrow_mut().set_cdr(args[1].clone())?;
+    Ok(args[0].clone())
+}
+
+fn list(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    Ok(arena.list(args.to_vec()))
+}
+
+fn is_atom(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("atom?", args, 1, Some(1))?;
+    let result = !matches!(&*upgrade(&args[0])?.borrow(), RispExp::Cons{..});
+    Ok(arena.boolean(result))
+}
+
+fn is_null(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("null?", args, 1, Some(1))?;
+    let result = upgrade(&args[0])?.borrow().is_nil();
+    Ok(arena.boolean(result))
+}
+
+fn is_nil(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("nil?", args, 1, Some(1))?;
+    let result = upgrade(&args[0])?.borrow().is_nil();
+    Ok(arena.boolean(result))
+}
+
+fn is_eq(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("eq?", args, 2, Some(2))?;
+    let result = equal::eq(&args[0], &args[1]);
+    Ok(arena.boolean(result))
+}
+
+fn is_eqv(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("eqv?", args, 2, Some(2))?;
+    let result = equal::eqv(&args[0], &args[1])?;
+    Ok(arena.boolean(result))
+}
+
+fn is_equal(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("equal?", args, 2, Some(2))?;
+    let result = equal::equal(&args[0], &args[1])?;
+    Ok(arena.boolean(result))
+}
+
+fn string_length(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("string-length", args, 1, Some(1))?;
+    let len = string_arg(&args[0])?.chars().count();
+    Ok(arena.alloc((len as i64).into()))
+}
+
+/// `(substring s start [end])`, indexing by characters.
+fn substring(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("substring", args, 2, Some(3))?;
+    let chars = string_arg(&args[0])?.chars().collect::<Vec<_>>();
+    let start = index_arg(&args[1])?;
+    let end = args.get(2).map(index_arg).transpose()?.unwrap_or(chars.len());
+    if end > chars.len() {
+        return Err(RispError::IndexOutOfRange { index: end, len: chars.len() });
+    }
+    if start > end {
+        return Err(RispError::InvalidRange { start, end });
+    }
+    Ok(string_exp(arena, chars[start..end].iter().collect()))
+}
+
+fn string_append(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    let s = args.iter().map(string_arg).collect::<Result<String>>()?;
+    Ok(string_exp(arena, s))
+}
+
+fn string_to_symbol(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("string->symbol", args, 1, Some(1))?;
+    let s = string_arg(&args[0])?;
+    Ok(arena.symbol(&s))
+}
+
+fn symbol_to_string(arena: &mut Arena, args: &[RispExpRef]) -> Result<RispExpRef> {
+    check_arity("symbol->string", args, 1, Some(1))?;
+    let name = crate::eval::expect_symbol(&args[0])?;
+    Ok(string_exp(arena, name.name().to_string()))
+}
@@ -0,0 +1,87 @@
+use std::{cell::RefCell, collections::{HashMap, HashSet}, rc::{Rc, Weak}};
+
+use crate::{arena::RispExpRef, symbol::SymbolId};
+
+#[derive(Debug, Default)]
+struct Frame {
+    vars: HashMap<SymbolId, RispExpRef>,
+    parent: Option<Env>,
+}
+
+/// A lexical environment: a chain of frames mapping interned symbols to arena
+/// cells. Cloning an `Env` shares the frame, so closures see later defines.
+#[derive(Debug, Clone, Default)]
+pub struct Env(Rc<RefCell<Frame>>);
+
+/// An [`Env`] that does not keep its frames alive, for references that
+/// would otherwise form a cycle through a frame's own keys.
+#[derive(Debug, Clone)]
+pub struct WeakEnv(Weak<RefCell<Frame>>);
+
+impl WeakEnv {
+    pub fn upgrade(&self) -> Option<Env> {
+        self.0.upgrade().map(Env)
+    }
+}
+
+impl Env {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn downgrade(&self) -> WeakEnv {
+        WeakEnv(Rc::downgrade(&self.0))
+    }
+
+    pub fn extend(&self) -> Self {
+        Env(Rc::new(RefCell::new(Frame { vars: HashMap::new(), parent: Some(self.clone()) })))
+    }
+
+    pub fn get(&self, name: &SymbolId) -> Option<RispExpRef> {
+        let frame = self.0.borrow();
+        match (frame.vars.get(name), &frame.parent) {
+            (Some(val), _) => Some(val.clone()),
+            (None, Some(parent)) => parent.get(name),
+            // a renamed identifier bound nowhere since its expansion
+            // means what it meant where the macro was defined
+            (None, None) => name.renamed().and_then(|(original, env)| env?.get(original)),
+        }
+    }
+
+    /// Empties this frame and makes it a child of `parent`, if nothing else
+    /// refers to it, so that a tail call can take over the frame of the call
+    /// it ends.
+    pub(crate) fn reuse(&self, parent: &Env) -> Option<Env> {
+        if Rc::strong_count(&self.0) != 1 || Rc::weak_count(&self.0) != 0 {
+            return None;
+        }
+        let mut frame = self.0.borrow_mut();
+        frame.vars.clear();
+        frame.parent = Some(parent.clone());
+        Some(self.clone())
+    }
+
+    pub fn define(&self, name: SymbolId, val: RispExpRef) {
+        self.0.borrow_mut().vars.insert(name, val);
+    }
+
+    /// Every value bound in this environment or its parents, for use as
+    /// garbage collection roots.
+    pub fn roots(&self) -> Vec<RispExpRef> {
+        let mut roots = Vec::new();
+        self.trace(&mut HashSet::new(), &mut roots);
+        roots
+    }
+
+    pub(crate) fn trace(&self, marked: &mut HashSet<*const ()>, stack: &mut Vec<RispExpRef>) {
+        let mut env = Some(self.clone());
+        while let Some(e) = env {
+            if !marked.insert(Rc::as_ptr(&e.0).cast()) {
+                break;
+            }
+            let frame = e.0.borrow();
+            stack.extend(frame.vars.values().cloned());
+            env = frame.parent.clone();
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! The three equality predicates: `eq` compares cell identity, `eqv` also
+//! compares atoms by value, and `equal` compares whole cons graphs,
+//! treating two cyclic structures as equal when they unfold to the same
+//! infinite tree. `PartialEq` and `Hash` on `RispExp` follow `equal`.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    mem,
+};
+
+use crate::{
+    arena::{upgrade, RispExpRef},
+    error::Result,
+    exp::{RispAtom, RispExp},
+};
+
+/// How many cells of the unfolded tree `Hash` looks at, so that cyclic
+/// and very long lists hash in bounded time.
+const HASH_BUDGET: usize = 64;
+
+/// Identity of arena cells.
+pub fn eq(a: &RispExpRef, b: &RispExpRef) -> bool {
+    a.ptr_eq(b)
+}
+
+/// Identity, or atoms with the same value.
+pub fn eqv(a: &RispExpRef, b: &RispExpRef) -> Result<bool> {
+    if a.ptr_eq(b) {
+        return Ok(true);
+    }
+    let (a, b) = (upgrade(a)?, upgrade(b)?);
+    let result = shallow_eqv(&a.borrow(), &b.borrow());
+    Ok(result)
+}
+
+/// Structural equality. Pairs of cells already being compared are assumed
+/// equal, which makes the walk terminate on cycles.
+pub fn equal(a: &RispExpRef, b: &RispExpRef) -> Result<bool> {
+    let mut assumed = HashSet::new();
+    let mut stack = vec![(a.clone(), b.clone())];
+    while let Some((a, b)) = stack.pop() {
+        if a.ptr_eq(&b) || !assumed.insert((a.as_ptr(), b.as_ptr())) {
+            continue;
+        }
+        let (a, b) = (upgrade(&a)?, upgrade(&b)?);
+        let (a, b) = (a.borrow(), b.borrow());
+        match (&*a, &*b) {
+            (RispExp::Cons{car: a_car, cdr: a_cdr}, RispExp::Cons{car: b_car, cdr: b_cdr}) => {
+                stack.push((a_cdr.clone(), b_cdr.clone()));
+                stack.push((a_car.clone(), b_car.clone()));
+            },
+            (a, b) if !shallow_eqv(a, b) => return Ok(false),
+            _ => (),
+        }
+    }
+    Ok(true)
+}
+
+/// `eqv` for two distinct cells. Floats compare by bits so that NaN equals
+/// itself and `Eq` stays reflexive.
+fn shallow_eqv(a: &RispExp, b: &RispExp) -> bool {
+    match (a, b) {
+        (RispExp::Nil, RispExp::Nil) => true,
+        (RispExp::Atom(RispAtom::Float(x)), RispExp::Atom(RispAtom::Float(y))) => x.to_bits() == y.to_bits(),
+        (RispExp::Atom(x), RispExp::Atom(y)) => x == y,
+        (RispExp::Builtin(x), RispExp::Builtin(y)) => x.name == y.name,
+        _ => false,
+    }
+}
+
+/// Compares like `equal`; a freed cell is only equal to itself.
+impl PartialEq for RispExp {
+    fn eq(&self, other: &Self) -> bool {
+        if std::ptr::eq(self, other) {
+            return true;
+        }
+        match (self, other) {
+            (RispExp::Cons{car: a_car, cdr: a_cdr}, RispExp::Cons{car: b_car, cdr: b_cdr}) => {
+                equal(a_car, b_car).unwrap_or(false) && equal(a_cdr, b_cdr).unwrap_or(false)
+            },
+            (a, b) => shallow_eqv(a, b),
+        }
+    }
+}
+
+impl Eq for RispExp {}
+
+/// Hashes the first `HASH_BUDGET` cells of the unfolded tree in
+/// breadth-first order. Graphs that are `equal` unfold to the same tree,
+/// so shared and cyclic structure hash consistently. Mutating a cell that
+/// is reachable from a hash table key changes its hash.
+impl Hash for RispExp {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_shallow(self, state);
+        let mut queue = VecDeque::new();
+        if let RispExp::Cons{car, cdr} = self {
+            queue.push_back(car.clone());
+            queue.push_back(cdr.clone());
+        }
+        for _ in 0..HASH_BUDGET {
+            let Some(exp) = queue.pop_front() else { break };
+            let Some(exp) = exp.upgrade() else {
+                state.write_u8(0xff);
+                continue;
+            };
+            let exp = exp.borrow();
+            hash_shallow(&exp, state);
+            if let RispExp::Cons{car, cdr} = &*exp {
+                queue.push_back(car.clone());
+                queue.push_back(cdr.clone());
+            }
+        }
+    }
+}
+
+fn hash_shallow<H: Hasher>(exp: &RispExp, state: &mut H) {
+    mem::discriminant(exp).hash(state);
+    match exp {
+        RispExp::Atom(atom) => {
+            mem::discriminant(atom).hash(state);
+            match atom {
+                RispAtom::Int(i) => i.hash(state),
+                RispAtom::BigInt(i) => i.hash(state),
+                RispAtom::Rational(r) => r.hash(state),
+                RispAtom::Float(x) => x.to_bits().hash(state),
+                RispAtom::Bool(b) => b.hash(state),
+                RispAtom::Str(s) => s.hash(state),
+                RispAtom::Char(c) => c.hash(state),
+                RispAtom::Symbol(s) => s.hash(state),
+            }
+        },
+        RispExp::Builtin(b) => b.name.hash(state),
+        // procedures and macros are only equal to themselves; cons cells hash their children
+        RispExp::Nil | RispExp::Cons{..} | RispExp::Lambda(_) | RispExp::Macro(_) | RispExp::Syntax(_)
+        | RispExp::Closure(_) | RispExp::Continuation(_) => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{hash_map::DefaultHasher, HashMap};
+
+    use super::*;
+    use crate::{arena::Arena, reader};
+
+    fn hash_of(exp: &RispExpRef) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        upgrade(exp).unwrap().borrow().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn set_cdr(cell: &RispExpRef, val: &RispExpRef) {
+        upgrade(cell).unwrap().borrow_mut().set_cdr(val.clone()).unwrap();
+    }
+
+    #[test]
+    fn test_eq_eqv() {
+        let mut arena = Arena::new();
+        let a = reader::read_one(&mut arena, "(1 2)").unwrap();
+        let b = reader::read_one(&mut arena, "(1 2)").unwrap();
+        let one = arena.alloc(1.into());
+        let other_one = arena.alloc(1.into());
+        let float = arena.alloc(1.0.into());
+
+        assert!(eq(&a, &a));
+        assert!(!eq(&a, &b));
+        assert!(!eq(&one, &other_one));
+        assert!(eq(&arena.symbol("x"), &arena.symbol("x")));
+        assert!(eqv(&one, &other_one).unwrap());
+        assert!(!eqv(&one, &float).unwrap());
+        assert!(!eqv(&a, &b).unwrap());
+        assert!(eqv(&arena.nil(), &arena.alloc(RispExp::Nil)).unwrap());
+    }
+
+    #[test]
+    fn test_equal() {
+        let mut arena = Arena::new();
+        let a = reader::read_one(&mut arena, r#"(1 (2 "s") . #\c)"#).unwrap();
+        let b = reader::read_one(&mut arena, r#"(1 (2 "s") . #\c)"#).unwrap();
+        let c = reader::read_one(&mut arena, r#"(1 (2 "t") . #\c)"#).unwrap();
+        assert!(equal(&a, &b).unwrap());
+        assert!(!equal(&a, &c).unwrap());
+        assert_eq!(*upgrade(&a).unwrap().borrow(), *upgrade(&b).unwrap().borrow());
+        assert_ne!(*upgrade(&a).unwrap().borrow(), *upgrade(&c).unwrap().borrow());
+        assert_eq!(hash_of(&a), hash_of(&b));
+
+        // a shared sublist is equal to a copied one
+        let x = reader::read_one(&mut arena, "(1 2)").unwrap();
+        let shared = arena.list(vec![x.clone(), x]);
+        let copied = reader::read_one(&mut arena, "((1 2) (1 2))").unwrap();
+        assert!(equal(&shared, &copied).unwrap());
+        assert_eq!(hash_of(&shared), hash_of(&copied));
+    }
+
+    #[test]
+    fn test_equal_cycle() {
+        let mut arena = Arena::new();
+        // #0=(1 . #0#)
+        let a = reader::read_one(&mut arena, "(1)").unwrap();
+        set_cdr(&a, &a);
+        // (1 . #0=(1 . #0#))
+        let inner = reader::read_one(&mut arena, "(1)").unwrap();
+        set_cdr(&inner, &inner);
+        let one = arena.alloc(1.into());
+        let b = arena.alloc((one, inner).into());
+        // #0=(1 2 . #0#)
+        let c = reader::read_one(&mut arena, "(1 2)").unwrap();
+        let c_last = upgrade(&c).unwrap().borrow().cdr_weak().unwrap();
+        set_cdr(&c_last, &c);
+
+        assert!(equal(&a, &b).unwrap());
+        assert_eq!(hash_of(&a), hash_of(&b));
+        assert!(!equal(&a, &c).unwrap());
+    }
+
+    #[test]
+    #[allow(clippy::mutable_key_type)]
+    fn test_hash_key() {
+        let mut arena = Arena::new();
+        let mut counts = HashMap::new();
+        for src in ["(a b)", "(a b)", "(a . b)", "1", "1", "1.0"] {
+            let exp = reader::read_one(&mut arena, src).unwrap();
+            let key = match &*upgrade(&exp).unwrap().borrow() {
+                RispExp::Cons{car, cdr} => RispExp::from((car, cdr)),
+                RispExp::Atom(atom) => RispExp::Atom(atom.clone()),
+                exp => panic!("unexpected {}", exp),
+            };
+            *counts.entry(key).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts[&RispExp::from(1)], 2);
+        assert_eq!(counts[&RispExp::from(1.0)], 1);
+    }
+
+    #[test]
+    fn test_freed() {
+        let mut arena = Arena::new();
+        let a = reader::read_one(&mut arena, "(1 2)").unwrap();
+        let b = reader::read_one(&mut arena, "(1 2)").unwrap();
+        arena.collect([&b]);
+        assert!(equal(&a, &b).is_err());
+        assert!(equal(&b, &b).unwrap());
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt::Display;
+
+use crate::{arena::DanglingRef, exp::RispExp, reader::ParseError};
+
+pub type Result<T> = std::result::Result<T, RispError>;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RispError {
+    WrongType {
+        expected: &'static str,
+        actual: &'static str,
+        value: String,
+    },
+    Arity {
+        name: String,
+        min: usize,
+        max: Option<usize>,
+        got: usize,
+    },
+    Unbound(String),
+    Arithmetic(&'static str),
+    Syntax(&'static str),
+    IndexOutOfRange {
+        index: usize,
+        len: usize,
+    },
+    /// A range whose start comes after its end.
+    InvalidRange {
+        start: usize,
+        end: usize,
+    },
+    DanglingRef,
+    CircularList,
+    Parse(ParseError),
+}
+
+impl RispError {
+    pub fn wrong_type(expected: &'static str, exp: &RispExp) -> Self {
+        RispError::WrongType { expected, actual: exp.type_name(), value: exp.to_string() }
+    }
+
+    pub fn arity(name: &str, min: usize, max: Option<usize>, got: usize) -> Self {
+        RispError::Arity { name: name.to_string(), min, max, got }
+    }
+}
+
+impl Display for RispError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RispError::WrongType { expected, actual, value } => {
+                write!(f, "wrong type: expected {}, got {} {}", expected, actual, value)
+            },
+            RispError::Arity { name, min, max, got } => {
+                write!(f, "{}: expected ", name)?;
+                match max {
+                    Some(max) if max == min => write!(f, "{}", min)?,
+                    Some(max) => write!(f, "{} to {}", min, max)?,
+                    None => write!(f, "at least {}", min)?,
+                }
+                write!(f, " arguments, got {}", got)
+            },
+            RispError::Unbound(name) => write!(f, "unbound symbol: {}", name),
+            RispError::Arithmetic(msg) => write!(f, "{}", msg),
+            RispError::Syntax(msg) => write!(f, "bad syntax: {}", msg),
+            RispError::IndexOutOfRange { index, len } => {
+                write!(f, "index {} out of range for length {}", index, len)
+            },
+            RispError::InvalidRange { start, end } => write!(f, "range start {} is after its end {}", start, end),
+            RispError::DanglingRef => write!(f, "{}", DanglingRef),
+            RispError::CircularList => write!(f, "circular list"),
+            RispError::Parse(e) => write!(f, "parse error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RispError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RispError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DanglingRef> for RispError {
+    fn from(_: DanglingRef) -> Self {
+        RispError::DanglingRef
+    }
+}
+
+impl From<ParseError> for RispError {
+    fn from(e: ParseError) -> Self {
+        RispError::Parse(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Arena;
+
+    #[test]
+    fn test_display() {
+        let mut arena = Arena::new();
+        let v1 = arena.alloc(1.into());
+        let err = v1.upgrade().unwrap().borrow().car().unwrap_err();
+        assert_eq!(err, RispError::WrongType { expected: "cons", actual: "int", value: "1".to_string() });
+        assert_eq!(err.to_string(), "wrong type: expected cons, got int 1");
+
+        assert_eq!(RispError::arity("car", 1, Some(1), 2).to_string(), "car: expected 1 arguments, got 2");
+        assert_eq!(RispError::arity("if", 2, Some(3), 1).to_string(), "if: expected 2 to 3 arguments, got 1");
+        assert_eq!(RispError::arity("+", 1, None, 0).to_string(), "+: expected at least 1 arguments, got 0");
+        assert_eq!(RispError::Unbound("x".to_string()).to_string(), "unbound symbol: x");
+        assert_eq!(
+            RispError::IndexOutOfRange { index: 4, len: 3 }.to_string(),
+            "index 4 out of range for length 3",
+        );
+        assert_eq!(RispError::InvalidRange { start: 2, end: 1 }.to_string(), "range start 2 is after its end 1");
+        assert_eq!(RispError::DanglingRef.to_string(), "dangling reference to a freed cell");
+
+        let err: RispError = crate::reader::read(&mut arena, ")").unwrap_err().into();
+        assert_eq!(err.to_string(), "parse error: 1:1: unexpected ')'");
+    }
+}
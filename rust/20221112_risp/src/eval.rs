@@ -0,0 +1,737 @@
+use crate::{
+    arena::{upgrade, Arena, RispExpRef},
+    builtins::{self, check_arity},
+    env::Env,
+    error::{Result, RispError},
+    exp::{Lambda, RispAtom, RispExp},
+    reader,
+    symbol::SymbolId,
+    syntax::{SyntaxRules, PRELUDE},
+};
+
+/// Returns the symbol if `exp` is a symbol.
+pub(crate) fn symbol_name(exp: &RispExpRef) -> Result<Option<SymbolId>> {
+    match &*upgrade(exp)?.borrow() {
+        RispExp::Atom(RispAtom::Symbol(s)) => Ok(Some(s.clone())),
+        _ => Ok(None),
+    }
+}
+
+pub(crate) fn expect_symbol(exp: &RispExpRef) -> Result<SymbolId> {
+    symbol_name(exp)?.ok_or_else(|| match upgrade(exp) {
+        Ok(exp) => RispError::wrong_type("symbol", &exp.borrow()),
+        Err(e) => e.into(),
+    })
+}
+
+/// Collects the elements of a proper list.
+pub fn list_to_vec(exp: &RispExpRef) -> Result<Vec<RispExpRef>> {
+    upgrade(exp)?.borrow().iter().collect()
+}
+
+pub(crate) fn is_true(exp: &RispExpRef) -> Result<bool> {
+    Ok(upgrade(exp)?.borrow().is_true())
+}
+
+/// Creates the global environment with every builtin bound.
+pub fn global_env(arena: &mut Arena) -> Env {
+    let env = Env::new();
+    for builtin in builtins::BUILTINS {
+        let val = arena.alloc(RispExp::Builtin(*builtin));
+        env.define(arena.intern(builtin.name), val);
+    }
+    let prelude = reader::read(arena, PRELUDE).expect("prelude parses");
+    for exp in prelude {
+        eval(arena, &exp, &env).expect("prelude evaluates");
+    }
+    env
+}
+
+/// What is left of evaluating a form: its value, or the form in tail
+/// position, which `eval` loops on instead of recursing so tail calls run in
+/// constant Rust stack space.
+enum Step {
+    Done(RispExpRef),
+    Eval(RispExpRef, Env),
+}
+
+/// How many cells the outermost `eval` allocates before it first collects.
+const GC_THRESHOLD: usize = 10_000;
+
+/// Evaluates `exp` in `env`. Tail calls run in constant Rust stack and, in
+/// the outermost call, constant heap: a tail call takes over the frame of
+/// the call it ends when nothing else refers to it, and between steps the
+/// cells allocated since `eval` started that the current form and
+/// environments no longer reach are collected. Nested calls never collect,
+/// since their callers hold handles in Rust frames the collector cannot
+/// see, and cells older than the call are always kept.
+pub fn eval(arena: &mut Arena, exp: &RispExpRef, env: &Env) -> Result<RispExpRef> {
+    arena.eval_depth += 1;
+    let result = eval_loop(arena, exp, env);
+    arena.eval_depth -= 1;
+    result
+}
+
+fn eval_loop(arena: &mut Arena, exp: &RispExpRef, env: &Env) -> Result<RispExpRef> {
+    let (mut exp, mut env) = (exp.clone(), env.clone());
+    let (start, outer) = (arena.len(), env.clone());
+    let mut limit = start + GC_THRESHOLD;
+    loop {
+        match eval_step(arena, &exp, &env)? {
+            Step::Done(val) => return Ok(val),
+            Step::Eval(next, next_env) => (exp, env) = (next, next_env),
+        }
+        if arena.eval_depth == 1 && arena.len() > limit {
+            let roots = [env.roots(), outer.roots(), vec![exp.clone()]].concat();
+            arena.collect_since(start, &roots);
+            limit = arena.len() + GC_THRESHOLD.max(arena.len() - start);
+        }
+    }
+}
+
+fn eval_step(arena: &mut Arena, exp: &RispExpRef, env: &Env) -> Result<Step> {
+    let (head, rest) = match &*upgrade(exp)?.borrow() {
+        RispExp::Atom(RispAtom::Symbol(s)) => {
+            return env.get(s).map(Step::Done).ok_or_else(|| RispError::Unbound(s.to_string()));
+        },
+        RispExp::Cons{car, cdr} => (car.clone(), cdr.clone()),
+        _ => return Ok(Step::Done(exp.clone())),
+    };
+
+    if let Some(name) = symbol_name(&head)? {
+        let kw = arena.kw();
+        match name.base() {
+            s if *s == kw.quote => {
+                let args = list_to_vec(&rest)?;
+                check_arity("quote", &args, 1, Some(1))?;
+                return Ok(Step::Done(args[0].clone()));
+            },
+            s if *s == kw.if_ => {
+                let args = list_to_vec(&rest)?;
+                check_arity("if", &args, 2, Some(3))?;
+                let cond = eval(arena, &args[0], env)?;
+                return Ok(match (is_true(&cond)?, args.get(2)) {
+                    (true, _) => Step::Eval(args[1].clone(), env.clone()),
+                    (false, Some(alt)) => Step::Eval(alt.clone(), env.clone()),
+                    (false, None) => Step::Done(arena.nil()),
+                });
+            },
+            s if *s == kw.cond => return eval_cond(arena, &rest, env),
+            s if *s == kw.begin => return eval_body(arena, &list_to_vec(&rest)?, env),
+            s if *s == kw.and || *s == kw.or => {
+                // stop at the first false value for `and`, the first true one for `or`
+                let stop_when = *s == kw.or;
+                let args = list_to_vec(&rest)?;
+                let Some((last, init)) = args.split_last() else {
+                    return Ok(Step::Done(arena.boolean(!stop_when)));
+                };
+                for arg in init {
+                    let result = eval(arena, arg, env)?;
+                    if is_true(&result)? == stop_when {
+                        return Ok(Step::Done(result));
+                    }
+                }
+                return Ok(Step::Eval(last.clone(), env.clone()));
+            },
+            s if *s == kw.define => return eval_define(arena, &rest, env).map(Step::Done),
+            s if *s == kw.defmacro => {
+                let args = list_to_vec(&rest)?;
+                check_arity("defmacro", &args, 3, None)?;
+                let name = expect_symbol(&args[0])?;
+                let params = args[1].clone();
+                let body = upgrade(&rest)?.borrow().cdr()?.borrow().cdr_weak()?;
+                let val = arena.alloc(RispExp::Macro(Lambda { params, body, env: env.clone() }));
+                env.define(name.clone(), val);
+                return Ok(Step::Done(arena.symbol_cell(&name)));
+            },
+            s if *s == kw.define_syntax => {
+                let args = list_to_vec(&rest)?;
+                check_arity("define-syntax", &args, 2, Some(2))?;
+                let name = expect_symbol(&args[0])?;
+                let val = arena.alloc(RispExp::Syntax(SyntaxRules::new(arena, &args[1], env)?));
+                env.define(name.clone(), val);
+                return Ok(Step::Done(arena.symbol_cell(&name)));
+            },
+            s if *s == kw.quasiquote => {
+                let args = list_to_vec(&rest)?;
+                check_arity("quasiquote", &args, 1, Some(1))?;
+                return quasiquote(arena, &args[0], env, 0).map(Step::Done);
+            },
+            s if *s == kw.macroexpand || *s == kw.macroexpand_1 => {
+                let args = list_to_vec(&rest)?;
+                check_arity(name.name(), &args, 1, Some(1))?;
+                let mut form = eval(arena, &args[0], env)?;
+                while let Some(expansion) = macroexpand_1(arena, &form, env)? {
+                    form = expansion;
+                    if *s == kw.macroexpand_1 {
+                        break;
+                    }
+                }
+                return Ok(Step::Done(form));
+            },
+            s if *s == kw.lambda => {
+                let args = list_to_vec(&rest)?;
+                check_arity("lambda", &args, 2, None)?;
+                let params = args[0].clone();
+                let body = upgrade(&rest)?.borrow().cdr_weak()?;
+                return Ok(Step::Done(arena.alloc(RispExp::Lambda(Lambda { params, body, env: env.clone() }))));
+            },
+            _ => (),
+        }
+    }
+
+    if let Some(expansion) = macroexpand_1(arena, exp, env)? {
+        return Ok(Step::Eval(expansion, env.clone()));
+    }
+
+    let func = eval(arena, &head, env)?;
+    let args = list_to_vec(&rest)?
+        .iter()
+        .map(|arg| eval(arena, arg, env))
+        .collect::<Result<Vec<_>>>()?;
+    let lambda = match &*upgrade(&func)?.borrow() {
+        RispExp::Lambda(lambda) => Some(lambda.clone()),
+        _ => None,
+    };
+    match lambda {
+        Some(lambda) => {
+            let env = bind_params(arena, &lambda, &args, Some(env))?;
+            eval_body(arena, &list_to_vec(&lambda.body)?, &env)
+        },
+        None => apply(arena, &func, &args).map(Step::Done),
+    }
+}
+
+/// Evaluates all but the last of `forms`, which is left in tail position;
+/// nil if there are none.
+fn eval_body(arena: &mut Arena, forms: &[RispExpRef], env: &Env) -> Result<Step> {
+    let Some((last, init)) = forms.split_last() else {
+        return Ok(Step::Done(arena.nil()));
+    };
+    for form in init {
+        eval(arena, form, env)?;
+    }
+    Ok(Step::Eval(last.clone(), env.clone()))
+}
+
+/// `(cond (test body...) ... (else body...))`. A clause without a body
+/// yields its test value; nil if no clause matches.
+fn eval_cond(arena: &mut Arena, rest: &RispExpRef, env: &Env) -> Result<Step> {
+    for clause in list_to_vec(rest)? {
+        let forms = list_to_vec(&clause)?;
+        check_arity("cond clause", &forms, 1, None)?;
+        let test = match symbol_name(&forms[0])? {
+            Some(s) if *s.base() == arena.kw().else_ => arena.boolean(true),
+            _ => eval(arena, &forms[0], env)?,
+        };
+        if is_true(&test)? {
+            if forms.len() == 1 {
+                return Ok(Step::Done(test));
+            }
+            return eval_body(arena, &forms[1..], env);
+        }
+    }
+    Ok(Step::Done(arena.nil()))
+}
+
+/// Expands `form` once if it calls a `defmacro` or `syntax-rules` macro
+/// bound in `env`.
+fn macroexpand_1(arena: &mut Arena, form: &RispExpRef, env: &Env) -> Result<Option<RispExpRef>> {
+    let (head, rest) = match &*upgrade(form)?.borrow() {
+        RispExp::Cons{car, cdr} => (car.clone(), cdr.clone()),
+        _ => return Ok(None),
+    };
+    let Some(val) = symbol_name(&head)?.and_then(|name| env.get(&name)) else {
+        return Ok(None);
+    };
+    let expansion = match &*upgrade(&val)?.borrow() {
+        RispExp::Macro(mac) => call_lambda(arena, mac, &list_to_vec(&rest)?)?,
+        RispExp::Syntax(rules) => rules.expand(arena, form)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(expansion))
+}
+
+/// Fills in the template of `` `tmpl ``. Only unquotes at depth 0 are
+/// evaluated: a nested quasiquote raises the depth and each unquote lowers
+/// it, so inner templates come out with their unquotes intact.
+fn quasiquote(arena: &mut Arena, tmpl: &RispExpRef, env: &Env, depth: usize) -> Result<RispExpRef> {
+    let kw = arena.kw();
+    if let Some(arg) = form_arg(tmpl, &kw.unquote)? {
+        if depth == 0 {
+            return eval(arena, &arg, env);
+        }
+        return requote(arena, &kw.unquote, &arg, env, depth - 1);
+    }
+    if let Some(arg) = form_arg(tmpl, &kw.unquote_splicing)? {
+        if depth == 0 {
+            return Err(RispError::Syntax("unquote-splicing outside a list"));
+        }
+        return requote(arena, &kw.unquote_splicing, &arg, env, depth - 1);
+    }
+    if let Some(arg) = form_arg(tmpl, &kw.quasiquote)? {
+        return requote(arena, &kw.quasiquote, &arg, env, depth + 1);
+    }
+
+    let (car, cdr) = match &*upgrade(tmpl)?.borrow() {
+        RispExp::Cons{car, cdr} => (car.clone(), cdr.clone()),
+        _ => return Ok(tmpl.clone()),
+    };
+    let items = match form_arg(&car, &kw.unquote_splicing)? {
+        Some(arg) if depth == 0 => list_to_vec(&eval(arena, &arg, env)?)?,
+        _ => vec![quasiquote(arena, &car, env, depth)?],
+    };
+    let rest = quasiquote(arena, &cdr, env, depth)?;
+    Ok(items.into_iter().rev().fold(rest, |cdr, car| arena.alloc((car, cdr).into())))
+}
+
+/// Rebuilds `(name arg)` around the filled-in `arg`.
+fn requote(arena: &mut Arena, name: &SymbolId, arg: &RispExpRef, env: &Env, depth: usize) -> Result<RispExpRef> {
+    let arg = quasiquote(arena, arg, env, depth)?;
+    let name = arena.symbol_cell(name);
+    Ok(arena.list([name, arg]))
+}
+
+/// The argument of `exp` if it is the form `(name arg)`.
+fn form_arg(exp: &RispExpRef, name: &SymbolId) -> Result<Option<RispExpRef>> {
+    let (head, rest) = match &*upgrade(exp)?.borrow() {
+        RispExp::Cons{car, cdr} => (car.clone(), cdr.clone()),
+        _ => return Ok(None),
+    };
+    if symbol_name(&head)?.is_none_or(|s| s.base() != name) {
+        return Ok(None);
+    }
+    let args = list_to_vec(&rest)?;
+    check_arity(name.name(), &args, 1, Some(1))?;
+    Ok(Some(args[0].clone()))
+}
+
+fn eval_define(arena: &mut Arena, rest: &RispExpRef, env: &Env) -> Result<RispExpRef> {
+    let args = list_to_vec(rest)?;
+    check_arity("define", &args, 2, None)?;
+    let target = args[0].clone();
+
+    // (define (name . params) body...)
+    let function = match &*upgrade(&target)?.borrow() {
+        RispExp::Cons{car, cdr} => Some((car.clone(), cdr.clone())),
+        _ => None,
+    };
+    let (name, val) = match function {
+        Some((name, params)) => {
+            let body = upgrade(rest)?.borrow().cdr_weak()?;
+            let lambda = arena.alloc(RispExp::Lambda(Lambda { params, body, env: env.clone() }));
+            (expect_symbol(&name)?, lambda)
+        },
+        None => {
+            check_arity("define", &args, 2, Some(2))?;
+            (expect_symbol(&target)?, eval(arena, &args[1], env)?)
+        },
+    };
+    env.define(name.clone(), val);
+    Ok(arena.symbol_cell(&name))
+}
+
+pub fn apply(arena: &mut Arena, func: &RispExpRef, args: &[RispExpRef]) -> Result<RispExpRef> {
+    // copied out of the cell, which the call may mutate as in (set-car! set-car! 1)
+    let builtin = match &*upgrade(func)?.borrow() {
+        RispExp::Builtin(builtin) => Some(*builtin),
+        _ => None,
+    };
+    if let Some(builtin) = builtin {
+        return (builtin.f)(arena, args);
+    }
+    let lambda = match &*upgrade(func)?.borrow() {
+        RispExp::Lambda(lambda) => lambda.clone(),
+        exp => return Err(RispError::wrong_type("procedure", exp)),
+    };
+    call_lambda(arena, &lambda, args)
+}
+
+fn call_lambda(arena: &mut Arena, lambda: &Lambda, args: &[RispExpRef]) -> Result<RispExpRef> {
+    let env = bind_params(arena, lambda, args, None)?;
+    match eval_body(arena, &list_to_vec(&lambda.body)?, &env)? {
+        Step::Done(val) => Ok(val),
+        Step::Eval(form, env) => eval(arena, &form, &env),
+    }
+}
+
+/// Binds `args` in a new frame for `lambda`, or in `frame` if the call
+/// ends the one that frame belongs to and it can be reused.
+fn bind_params(arena: &mut Arena, lambda: &Lambda, args: &[RispExpRef], frame: Option<&Env>) -> Result<Env> {
+    let env = frame.and_then(|frame| frame.reuse(&lambda.env)).unwrap_or_else(|| lambda.env.extend());
+    let mut params = lambda.params.clone();
+    let mut args = args.iter();
+    let mut required = 0;
+    loop {
+        let next = match &*upgrade(&params)?.borrow() {
+            RispExp::Cons{car, cdr} => {
+                let name = expect_symbol(car)?;
+                match args.next() {
+                    Some(arg) => env.define(name, arg.clone()),
+                    None => return Err(RispError::arity("lambda", required + 1, None, required)),
+                }
+                required += 1;
+                cdr.clone()
+            },
+            exp if exp.is_nil() => {
+                let rest = args.count();
+                if rest > 0 {
+                    return Err(RispError::arity("lambda", required, Some(required), required + rest));
+                }
+                break;
+            },
+            RispExp::Atom(RispAtom::Symbol(name)) => {
+                // (lambda (a . rest) ...) or (lambda args ...)
+                let rest = arena.list(args.cloned());
+                env.define(name.clone(), rest);
+                break;
+            },
+            exp => return Err(RispError::wrong_type("symbol", exp)),
+        };
+        params = next;
+    }
+    Ok(env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader;
+
+    fn eval_str(src: &str) -> Result<String> {
+        let mut arena = Arena::new();
+        let env = global_env(&mut arena);
+        let mut result = arena.nil();
+        for exp in reader::read(&mut arena, src)? {
+            result = eval(&mut arena, &exp, &env)?;
+        }
+        let s = upgrade(&result)?.borrow().to_string();
+        Ok(s)
+    }
+
+    #[test]
+    fn test_self_evaluating() {
+        assert_eq!(eval_str("42").unwrap(), "42");
+        assert_eq!(eval_str("nil").unwrap(), "nil");
+        assert_eq!(eval_str("'a").unwrap(), "a");
+        assert_eq!(eval_str("'(1 . 2)").unwrap(), "(1 . 2)");
+        assert_eq!(eval_str("car").unwrap(), "#<builtin car>");
+    }
+
+    #[test]
+    fn test_application() {
+        assert_eq!(eval_str("(+ 1 2 3)").unwrap(), "6");
+        assert_eq!(eval_str("(- 10 (* 2 3))").unwrap(), "4");
+        assert_eq!(eval_str("(cons 1 (list 2 3))").unwrap(), "(1 2 3)");
+        assert_eq!(eval_str("(car (cdr '(1 2 3)))").unwrap(), "2");
+        assert_eq!(eval_str("((lambda (x y) (+ x y)) 1 2)").unwrap(), "3");
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(eval_str("(* 99999999999 99999999999)").unwrap(), "9999999999800000000001");
+        assert_eq!(eval_str("(- (* 99999999999 99999999999) 9999999999800000000000)").unwrap(), "1");
+        assert_eq!(eval_str("(/ 1 3)").unwrap(), "1/3");
+        assert_eq!(eval_str("(+ 1/3 2/3)").unwrap(), "1");
+        assert_eq!(eval_str("(* 1.5 2)").unwrap(), "3.0");
+        assert_eq!(eval_str("(= 1/2 0.5)").unwrap(), "#t");
+        assert_eq!(eval_str("(< 1 3/2 2.0)").unwrap(), "#t");
+        assert_eq!(eval_str("(exact->inexact 1/8)").unwrap(), "0.125");
+        assert_eq!(eval_str("(inexact->exact 0.125)").unwrap(), "1/8");
+        assert_eq!(eval_str("(exact? (/ 4 2))").unwrap(), "#t");
+        assert_eq!(eval_str("(inexact? 1)").unwrap(), "#f");
+        assert_eq!(eval_str("(/ 1 0)").unwrap_err(), RispError::Arithmetic("division by zero"));
+        assert_eq!(eval_str("(+ 1 'a)").unwrap_err().to_string(), "wrong type: expected number, got symbol a");
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(eval_str(r#""hi""#).unwrap(), r#""hi""#);
+        assert_eq!(eval_str(r"#\a").unwrap(), r"#\a");
+        assert_eq!(eval_str(r#"(string-length "héllo")"#).unwrap(), "5");
+        assert_eq!(eval_str(r#"(substring "héllo" 1 3)"#).unwrap(), r#""él""#);
+        assert_eq!(eval_str(r#"(substring "hello" 2)"#).unwrap(), r#""llo""#);
+        assert_eq!(eval_str(r#"(string-append "a" "\"b\"" "")"#).unwrap(), r#""a\"b\"""#);
+        assert_eq!(eval_str(r#"(string->symbol "nil")"#).unwrap(), "nil");
+        assert_eq!(eval_str(r#"(null? (string->symbol "nil"))"#).unwrap(), "#t");
+        assert_eq!(eval_str(r#"(null? "nil")"#).unwrap(), "#f");
+        assert_eq!(eval_str("(symbol->string 'abc)").unwrap(), r#""abc""#);
+        assert_eq!(
+            eval_str(r#"(substring "abc" 1 4)"#).unwrap_err(),
+            RispError::IndexOutOfRange { index: 4, len: 3 },
+        );
+        assert_eq!(
+            eval_str(r#"(substring "abc" 2 1)"#).unwrap_err(),
+            RispError::InvalidRange { start: 2, end: 1 },
+        );
+        assert_eq!(
+            eval_str("(string-length 'a)").unwrap_err().to_string(),
+            "wrong type: expected string, got symbol a",
+        );
+    }
+
+    #[test]
+    fn test_nil() {
+        assert_eq!(eval_str("'()").unwrap(), "nil");
+        assert_eq!(eval_str("(list)").unwrap(), "nil");
+        assert_eq!(eval_str("'(1 . nil)").unwrap(), "(1)");
+        assert_eq!(eval_str("'(1 . ())").unwrap(), "(1)");
+        assert_eq!(eval_str("(car nil)").unwrap(), "nil");
+        assert_eq!(eval_str("(cdr '())").unwrap(), "nil");
+        assert_eq!(eval_str("(null? '())").unwrap(), "#t");
+        assert_eq!(eval_str("(nil? (cdr '(1)))").unwrap(), "#t");
+        assert_eq!(eval_str("(nil? 0)").unwrap(), "#f");
+        assert_eq!(eval_str("(eq? '() nil)").unwrap(), "#t");
+        assert_eq!(eval_str("(atom? nil)").unwrap(), "#t");
+        assert_eq!(
+            eval_str("(+ nil 1)").unwrap_err().to_string(),
+            "wrong type: expected number, got empty list nil",
+        );
+
+        let mut arena = Arena::new();
+        let nil = arena.nil();
+        assert_eq!(upgrade(&nil).unwrap().borrow().iter().count(), 0);
+        let lst = reader::read_one(&mut arena, "(1 2 . nil)").unwrap();
+        assert_eq!(upgrade(&lst).unwrap().borrow().iter().count(), 2);
+    }
+
+    #[test]
+    fn test_equality() {
+        assert_eq!(eval_str("(eq? 'a 'a)").unwrap(), "#t");
+        assert_eq!(eval_str("(eq? 1 1)").unwrap(), "#f");
+        assert_eq!(eval_str("(define x '(1)) (eq? x x)").unwrap(), "#t");
+        assert_eq!(eval_str("(eq? #t #t)").unwrap(), "#t");
+        assert_eq!(eval_str("(eq? (null? '()) #t)").unwrap(), "#t");
+        assert_eq!(eval_str("(eq? (and) (< 1 2))").unwrap(), "#t");
+        assert_eq!(eval_str("(eqv? 1 1)").unwrap(), "#t");
+        assert_eq!(eval_str("(eqv? 1 1.0)").unwrap(), "#f");
+        assert_eq!(eval_str("(eqv? '(1) '(1))").unwrap(), "#f");
+        assert_eq!(eval_str("(equal? '(1 (2 \"x\")) (list 1 (list 2 \"x\")))").unwrap(), "#t");
+        assert_eq!(eval_str("(equal? '(1 2) '(1 2 3))").unwrap(), "#f");
+        assert_eq!(eval_str("(equal? car car)").unwrap(), "#t");
+    }
+
+    #[test]
+    fn test_mutation() {
+        assert_eq!(eval_str("(define a '(5)) (define b (cons 6 a)) (define c (cons 10 a)) (set-car! a 9) (list b c)").unwrap(), "((6 . #0=(9)) (10 . #0#))");
+        assert_eq!(eval_str("(define x (list 1 2)) (set-cdr! x 3)").unwrap(), "(1 . 3)");
+        assert_eq!(eval_str("(define x (list 1)) (set-cdr! x x)").unwrap(), "#0=(1 . #0#)");
+        assert_eq!(eval_str("(set-car! 1 2)").unwrap_err().to_string(), "wrong type: expected cons, got int 1");
+        assert_eq!(eval_str("(set-cdr! '() 2)").unwrap_err().to_string(), "wrong type: expected cons, got empty list nil");
+        // the builtin's own cell is not borrowed while it runs
+        assert_eq!(eval_str("(set-car! set-car! 1)").unwrap_err().to_string(), "wrong type: expected cons, got procedure #<builtin set-car!>");
+    }
+
+    #[test]
+    fn test_if() {
+        assert_eq!(eval_str("(if (< 1 2) 'yes 'no)").unwrap(), "yes");
+        assert_eq!(eval_str("(if (< 2 1) 'yes 'no)").unwrap(), "no");
+        assert_eq!(eval_str("(if nil 'yes)").unwrap(), "nil");
+        assert_eq!(eval_str("(if 0 'yes 'no)").unwrap(), "yes");
+    }
+
+    #[test]
+    fn test_truthiness() {
+        assert_eq!(eval_str("(if #f 'yes 'no)").unwrap(), "no");
+        assert_eq!(eval_str("(if '() 'yes 'no)").unwrap(), "no");
+        assert_eq!(eval_str("(if \"\" 'yes 'no)").unwrap(), "yes");
+        assert_eq!(eval_str("(if 't 'yes 'no)").unwrap(), "yes");
+        // #t is the only truth constant; t is an ordinary symbol
+        assert_eq!(eval_str("t").unwrap_err(), RispError::Unbound("t".to_string()));
+        assert_eq!(eval_str("(< 2 1)").unwrap(), "#f");
+
+        assert_eq!(eval_str("(and)").unwrap(), "#t");
+        assert_eq!(eval_str("(and 1 2)").unwrap(), "2");
+        assert_eq!(eval_str("(and 1 #f (car 1))").unwrap(), "#f");
+        assert_eq!(eval_str("(or)").unwrap(), "#f");
+        assert_eq!(eval_str("(or nil 2 (car 1))").unwrap(), "2");
+        assert_eq!(eval_str("(or #f nil)").unwrap(), "nil");
+
+        let src = "
+            (define (sign n)
+              (cond ((< n 0) 'negative)
+                    ((= n 0) 'zero)
+                    (else 'positive)))
+            (list (sign -5) (sign 0) (sign 7))";
+        assert_eq!(eval_str(src).unwrap(), "(negative zero positive)");
+        assert_eq!(eval_str("(cond (#f 1) ((+ 1 2)))").unwrap(), "3");
+        assert_eq!(eval_str("(cond (#f 1))").unwrap(), "nil");
+        assert_eq!(eval_str("(cond ())").unwrap_err(), RispError::arity("cond clause", 1, None, 0));
+    }
+
+    #[test]
+    fn test_define() {
+        assert_eq!(eval_str("(define x 10) (* x x)").unwrap(), "100");
+        assert_eq!(eval_str("(define (sq x) (* x x))").unwrap(), "sq");
+        assert_eq!(eval_str("(define (sq x) (* x x)) (sq 7)").unwrap(), "49");
+        assert_eq!(
+            eval_str("(define (fact n) (if (= n 0) 1 (* n (fact (- n 1))))) (fact 10)").unwrap(),
+            "3628800",
+        );
+    }
+
+    #[test]
+    fn test_closure() {
+        let src = "
+            (define (make-adder n) (lambda (x) (+ x n)))
+            (define add3 (make-adder 3))
+            (add3 4)";
+        assert_eq!(eval_str(src).unwrap(), "7");
+
+        // body sees definitions made after the closure was created
+        assert_eq!(eval_str("(define (f) y) (define y 5) (f)").unwrap(), "5");
+    }
+
+    #[test]
+    fn test_begin() {
+        assert_eq!(eval_str("(begin (define x 1) (define x (+ x 1)) x)").unwrap(), "2");
+        assert_eq!(eval_str("(begin)").unwrap(), "nil");
+        assert_eq!(eval_str("(and 1 2)").unwrap(), "2");
+        assert_eq!(eval_str("(or #f)").unwrap(), "#f");
+        assert_eq!(eval_str("(cond (#f 1) (2))").unwrap(), "2");
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, ignore = "slow in debug builds; run with --release")]
+    fn test_count_down() {
+        let src = "
+            (define (count-down n) (if (= n 0) 'done (count-down (- n 1))))
+            (count-down 1000000)";
+        assert_eq!(eval_str(src).unwrap(), "done");
+    }
+
+    #[test]
+    fn test_tail_calls() {
+        // the other tail positions, and mutual recursion; deep enough to
+        // overflow the stack without tail calls
+        let src = "
+            (define (count-cond n) (cond ((= n 0) 'done) (else (count-cond (- n 1)))))
+            (define (count-begin n) (begin (if (= n 0) 'done (count-begin (- n 1)))))
+            (define (count-and n) (or (= n 0) (and #t (count-and (- n 1)))))
+            (define (count-let n) (let ((m (- n 1))) (if (< m 0) 'done (count-let m))))
+            (define (even? n) (if (= n 0) #t (odd? (- n 1))))
+            (define (odd? n) (if (= n 0) #f (even? (- n 1))))
+            (list (count-cond 20000) (count-begin 20000) (count-and 20000) (count-let 20000) (even? 20001))";
+        assert_eq!(eval_str(src).unwrap(), "(done done #t done #f)");
+
+        // a reused frame is never one a closure still holds
+        let src = "
+            (define (thunks n acc) (if (= n 0) acc (thunks (- n 1) (cons (lambda () n) acc))))
+            (define ts (thunks 3 nil))
+            (list ((car ts)) ((car (cdr ts))) ((car (cdr (cdr ts)))))";
+        assert_eq!(eval_str(src).unwrap(), "(1 2 3)");
+    }
+
+    #[test]
+    fn test_tail_call_heap() -> Result<()> {
+        let mut arena = Arena::new();
+        let env = global_env(&mut arena);
+        let mut forms = reader::read(&mut arena, "
+            (define (count-down n) (if (= n 0) 'done (count-down (- n 1))))
+            (count-down 100000)")?;
+        let call = forms.pop().unwrap();
+        eval(&mut arena, &forms[0], &env)?;
+
+        let before = arena.len();
+        let result = eval(&mut arena, &call, &env)?;
+        assert_eq!(upgrade(&result)?.borrow().to_string(), "done");
+        assert!(arena.len() - before < 2 * GC_THRESHOLD, "{} cells left", arena.len() - before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_quasiquote() {
+        assert_eq!(eval_str("`(a b)").unwrap(), "(a b)");
+        assert_eq!(eval_str("(define x 2) `(1 ,x ,@(list 3 4) 5)").unwrap(), "(1 2 3 4 5)");
+        assert_eq!(eval_str("(define x 2) `(1 . ,x)").unwrap(), "(1 . 2)");
+        assert_eq!(eval_str("`(1 ,@'() 2 ,@(list 3))").unwrap(), "(1 2 3)");
+        assert_eq!(eval_str("`,(+ 1 2)").unwrap(), "3");
+        // only the innermost level is filled in
+        assert_eq!(
+            eval_str("(define x 1) `(a `(b ,(c ,x)))").unwrap(),
+            "(a (quasiquote (b (unquote (c 1)))))",
+        );
+        assert_eq!(eval_str("`,@(list 1)").unwrap_err(), RispError::Syntax("unquote-splicing outside a list"));
+        assert_eq!(
+            eval_str("`(1 ,@2)").unwrap_err().to_string(),
+            "wrong type: expected list, got int 2",
+        );
+    }
+
+    #[test]
+    fn test_defmacro() {
+        let src = "
+            (defmacro unless (test . body) `(cond (,test nil) (else ,@body)))
+            (define (f x) (unless (< x 0) 'non-negative))
+            (list (f 1) (f -1))";
+        assert_eq!(eval_str(src).unwrap(), "(non-negative nil)");
+
+        // arguments arrive unevaluated, so a macro can decide what runs
+        let src = "
+            (defmacro my-or2 (a b) `(cond (,a) (else ,b)))
+            (my-or2 (car '(1)) (undefined))";
+        assert_eq!(eval_str(src).unwrap(), "1");
+
+        // macros expanding into other macros
+        let src = "
+            (defmacro when (test . body) `(cond (,test ,@body)))
+            (defmacro when-not (test . body) `(when (not ,test) ,@body))
+            (define (not x) (if x #f #t))
+            (when-not #f 1 2)";
+        assert_eq!(eval_str(src).unwrap(), "2");
+
+        assert_eq!(eval_str("(defmacro m (x) x) m").unwrap(), "#<macro>");
+        assert_eq!(
+            eval_str("(defmacro m (x) x) (car m)").unwrap_err().to_string(),
+            "wrong type: expected cons, got macro #<macro>",
+        );
+    }
+
+    #[test]
+    fn test_macroexpand() {
+        let defs = "(defmacro when (test . body) `(cond (,test ,@body)))
+                    (defmacro when2 (test x) `(when ,test ,x))";
+        assert_eq!(
+            eval_str(&format!("{} (macroexpand-1 '(when2 a b))", defs)).unwrap(),
+            "(when a b)",
+        );
+        assert_eq!(
+            eval_str(&format!("{} (macroexpand '(when2 a b))", defs)).unwrap(),
+            "(cond (a b))",
+        );
+        assert_eq!(eval_str(&format!("{} (macroexpand '(+ 1 2))", defs)).unwrap(), "(+ 1 2)");
+        assert_eq!(eval_str("(macroexpand 5)").unwrap(), "5");
+    }
+
+    #[test]
+    fn test_variadic() {
+        assert_eq!(eval_str("((lambda args args) 1 2 3)").unwrap(), "(1 2 3)");
+        assert_eq!(eval_str("((lambda (a . rest) rest) 1 2 3)").unwrap(), "(2 3)");
+        assert_eq!(eval_str("((lambda (a . rest) rest) 1)").unwrap(), "nil");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(eval_str("x").unwrap_err(), RispError::Unbound("x".to_string()));
+        assert_eq!(eval_str("((lambda (x) x))").unwrap_err(), RispError::arity("lambda", 1, None, 0));
+        assert_eq!(eval_str("((lambda (x) x) 1 2)").unwrap_err(), RispError::arity("lambda", 1, Some(1), 2));
+        assert_eq!(eval_str("(car 1)").unwrap_err().to_string(), "wrong type: expected cons, got int 1");
+        assert_eq!(eval_str("(1 2)").unwrap_err().to_string(), "wrong type: expected procedure, got int 1");
+        assert_eq!(eval_str("(if)").unwrap_err(), RispError::arity("if", 2, Some(3), 0));
+        assert!(matches!(eval_str("(").unwrap_err(), RispError::Parse(_)));
+    }
+
+    #[test]
+    fn test_collect_keeps_env() {
+        let mut arena = Arena::new();
+        let env = global_env(&mut arena);
+        for exp in reader::read(&mut arena, "(define (make-adder n) (lambda (x) (+ x n))) (define add3 (make-adder 3))").unwrap() {
+            eval(&mut arena, &exp, &env).unwrap();
+        }
+        assert!(arena.collect(env.roots().iter()) > 0);
+
+        let exp = reader::read_one(&mut arena, "(add3 4)").unwrap();
+        let result = eval(&mut arena, &exp, &env).unwrap();
+        assert_eq!(upgrade(&result).unwrap().borrow().to_string(), "7");
+    }
+}
@@ -0,0 +1,313 @@
+use std::fmt::{Debug, Display, Write};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
+
+use crate::{arena::{upgrade, Arena, RispExpRef, RispExpRefStrong}, env::Env, error::{Result, RispError}, symbol::SymbolId, secd::{Closure, Continuation}, syntax::SyntaxRules};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RispAtom {
+    Int(i64),
+    BigInt(BigInt),
+    Rational(BigRational),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    Char(char),
+    Symbol(SymbolId),
+}
+
+/// Characters written by name rather than literally after `#\`.
+const CHAR_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("nul", '\0'),
+];
+
+pub(crate) fn char_named(name: &str) -> Option<char> {
+    CHAR_NAMES.iter().find(|(n, _)| *n == name).map(|(_, c)| *c)
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            '\r' => f.write_str("\\r")?,
+            '\0' => f.write_str("\\0")?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl Display for RispAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RispAtom::Int(i) => write!(f, "{}", i),
+            RispAtom::BigInt(i) => write!(f, "{}", i),
+            RispAtom::Rational(r) => write!(f, "{}", r),
+            RispAtom::Float(x) => crate::num::fmt_float(f, *x),
+            RispAtom::Bool(b) => write!(f, "{}", if *b { "#t" } else { "#f" }),
+            RispAtom::Str(s) => write_string(f, s),
+            RispAtom::Char(c) => match CHAR_NAMES.iter().find(|(_, named)| named == c) {
+                Some((name, _)) => write!(f, "#\\{}", name),
+                None => write!(f, "#\\{}", c),
+            },
+            RispAtom::Symbol(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<i64> for RispAtom {
+    fn from(i: i64) -> Self {
+        RispAtom::Int(i)
+    }
+}
+
+impl From<BigInt> for RispAtom {
+    fn from(i: BigInt) -> Self {
+        match i.to_i64() {
+            Some(i) => RispAtom::Int(i),
+            None => RispAtom::BigInt(i),
+        }
+    }
+}
+
+impl From<BigRational> for RispAtom {
+    fn from(r: BigRational) -> Self {
+        if r.is_integer() {
+            r.to_integer().into()
+        } else {
+            RispAtom::Rational(r)
+        }
+    }
+}
+
+impl From<f64> for RispAtom {
+    fn from(f: f64) -> Self {
+        RispAtom::Float(f)
+    }
+}
+
+impl From<bool> for RispAtom {
+    fn from(b: bool) -> Self {
+        RispAtom::Bool(b)
+    }
+}
+
+impl From<char> for RispAtom {
+    fn from(c: char) -> Self {
+        RispAtom::Char(c)
+    }
+}
+
+impl From<SymbolId> for RispAtom {
+    fn from(id: SymbolId) -> Self {
+        RispAtom::Symbol(id)
+    }
+}
+
+pub type BuiltinFn = fn(&mut Arena, &[RispExpRef]) -> Result<RispExpRef>;
+
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub f: BuiltinFn,
+}
+
+impl Debug for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    pub params: RispExpRef,
+    pub body: RispExpRef,
+    pub env: Env,
+}
+
+#[derive(Debug)]
+pub enum RispExp {
+    /// The empty list, which also terminates every proper list. The arena
+    /// holds one canonical nil cell, and the symbol `nil` reads as it.
+    Nil,
+    Atom(RispAtom),
+    Cons{
+        car: RispExpRef,
+        cdr: RispExpRef,
+    },
+    Builtin(Builtin),
+    Lambda(Lambda),
+    /// Defined by `defmacro`: called with its unevaluated arguments, and the
+    /// form it returns is evaluated in place of the call.
+    Macro(Lambda),
+    /// Defined by `define-syntax` with `syntax-rules`.
+    Syntax(SyntaxRules),
+    /// Made by `ldf` on the SECD machine.
+    Closure(Closure),
+    /// Captured by `call/cc` on the SECD machine.
+    Continuation(Continuation),
+}
+
+impl<T> From<T> for RispExp where T: Into<RispAtom> {
+    fn from(t: T) -> Self {
+        RispExp::Atom(t.into())
+    }
+}
+
+impl From<(&RispExpRef, &RispExpRef)> for RispExp {
+    fn from((car, cdr): (&RispExpRef, &RispExpRef)) -> Self {
+        RispExp::Cons{car: car.clone(), cdr: cdr.clone()}
+    }
+}
+
+impl From<(RispExpRef, RispExpRef)> for RispExp {
+    fn from((car, cdr): (RispExpRef, RispExpRef)) -> Self {
+        RispExp::Cons{car, cdr}
+    }
+}
+
+impl RispExp {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            RispExp::Atom(RispAtom::Int(_) | RispAtom::BigInt(_)) => "int",
+            RispExp::Atom(RispAtom::Rational(_)) => "rational",
+            RispExp::Atom(RispAtom::Float(_)) => "float",
+            RispExp::Atom(RispAtom::Bool(_)) => "boolean",
+            RispExp::Atom(RispAtom::Str(_)) => "string",
+            RispExp::Atom(RispAtom::Char(_)) => "char",
+            RispExp::Atom(RispAtom::Symbol(_)) => "symbol",
+            RispExp::Nil => "empty list",
+            RispExp::Cons{..} => "cons",
+            RispExp::Builtin(_) | RispExp::Lambda(_) | RispExp::Closure(_) | RispExp::Continuation(_) => "procedure",
+            RispExp::Macro(_) => "macro",
+            RispExp::Syntax(_) => "syntax",
+        }
+    }
+
+    pub fn is_nil(&self) -> bool {
+        matches!(self, RispExp::Nil)
+    }
+
+    /// Truthiness used by every conditional form: `#f` and nil are false,
+    /// everything else, including 0, `""` and the symbol `t`, is true.
+    pub fn is_true(&self) -> bool {
+        !matches!(self, RispExp::Nil | RispExp::Atom(RispAtom::Bool(false)))
+    }
+
+    pub fn car(&self) -> Result<RispExpRefStrong> {
+        match self {
+            RispExp::Cons{car, ..} => Ok(upgrade(car)?),
+            _ => Err(RispError::wrong_type("cons", self)),
+        }
+    }
+
+    pub fn car_weak(&self) -> Result<RispExpRef> {
+        match self {
+            RispExp::Cons{car, ..} => Ok(car.clone()),
+            _ => Err(RispError::wrong_type("cons", self)),
+        }
+    }
+
+    pub fn car_weak_ref(&self) -> Result<&RispExpRef> {
+        match self {
+            RispExp::Cons{car, ..} => Ok(car),
+            _ => Err(RispError::wrong_type("cons", self)),
+        }
+    }
+
+    pub fn cdr(&self) -> Result<RispExpRefStrong> {
+        match self {
+            RispExp::Cons{cdr, ..} => Ok(upgrade(cdr)?),
+            _ => Err(RispError::wrong_type("cons", self)),
+        }
+    }
+
+    pub fn cdr_weak(&self) -> Result<RispExpRef> {
+        match self {
+            RispExp::Cons{cdr, ..} => Ok(cdr.clone()),
+            _ => Err(RispError::wrong_type("cons", self)),
+        }
+    }
+
+    pub fn cdr_weak_ref(&self) -> Result<&RispExpRef> {
+        match self {
+            RispExp::Cons{cdr, ..} => Ok(cdr),
+            _ => Err(RispError::wrong_type("cons", self)),
+        }
+    }
+
+    /// Points the car of this cons at `val`. Every list sharing the cell
+    /// sees the change.
+    pub fn set_car(&mut self, val: RispExpRef) -> Result<()> {
+        match self {
+            RispExp::Cons{car, ..} => {
+                *car = val;
+                Ok(())
+            },
+            _ => Err(RispError::wrong_type("cons", self)),
+        }
+    }
+
+    pub fn set_cdr(&mut self, val: RispExpRef) -> Result<()> {
+        match self {
+            RispExp::Cons{cdr, ..} => {
+                *cdr = val;
+                Ok(())
+            },
+            _ => Err(RispError::wrong_type("cons", self)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_car_cdr() {
+        let mut arena = Arena::new();
+        let nil = arena.nil();
+        let v1 = arena.alloc(5.into());
+        let v2 = arena.alloc(6.into());
+        let v3 = arena.alloc(10.into());
+
+        // b and c share a as their tail
+        let a = arena.alloc((&v1, &nil).into());
+        let b = arena.alloc((&v2, &a).into());
+        let c = arena.alloc((&v3, &a).into());
+        assert_eq!(b.upgrade().unwrap().borrow().to_string(), "(6 5)");
+        assert_eq!(c.upgrade().unwrap().borrow().to_string(), "(10 5)");
+
+        let x1 = arena.alloc(9.into());
+        a.upgrade().unwrap().borrow_mut().set_car(x1).unwrap();
+        assert_eq!(a.upgrade().unwrap().borrow().to_string(), "(9)");
+        assert_eq!(b.upgrade().unwrap().borrow().to_string(), "(6 9)");
+        assert_eq!(c.upgrade().unwrap().borrow().to_string(), "(10 9)");
+
+        let w = arena.alloc(43.into());
+        a.upgrade().unwrap().borrow_mut().set_cdr(w).unwrap();
+        assert_eq!(a.upgrade().unwrap().borrow().to_string(), "(9 . 43)");
+        assert_eq!(b.upgrade().unwrap().borrow().to_string(), "(6 9 . 43)");
+        assert_eq!(c.upgrade().unwrap().borrow().to_string(), "(10 9 . 43)");
+
+        // redirecting b's tail leaves c and a alone
+        b.upgrade().unwrap().borrow_mut().set_cdr(nil.clone()).unwrap();
+        assert_eq!(b.upgrade().unwrap().borrow().to_string(), "(6)");
+        assert_eq!(c.upgrade().unwrap().borrow().to_string(), "(10 9 . 43)");
+
+        let err = v1.upgrade().unwrap().borrow_mut().set_car(nil.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "wrong type: expected cons, got int 5");
+        let err = nil.upgrade().unwrap().borrow_mut().set_cdr(v1).unwrap_err();
+        assert_eq!(err.to_string(), "wrong type: expected cons, got empty list nil");
+    }
+}
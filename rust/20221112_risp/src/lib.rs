@@ -0,0 +1,35 @@
+// lets `risp!` name this crate as `::risp` from inside it too
+extern crate self as risp;
+
+mod arena;
+mod builtins;
+mod env;
+pub mod equal;
+mod error;
+mod eval;
+mod exp;
+pub mod list;
+mod num;
+mod printer;
+pub mod reader;
+mod repl;
+pub mod secd;
+mod symbol;
+mod syntax;
+
+pub use arena::{upgrade, Arena, Interpolate, DanglingRef, RispExpRef, RispExpRefStrong};
+pub use env::Env;
+pub use error::{Result, RispError};
+pub use eval::{apply, eval, global_env, list_to_vec};
+pub use exp::{Builtin, BuiltinFn, Lambda, RispAtom, RispExp};
+pub use repl::Repl;
+/// `risp!(arena, (ldc 'a 1 "s"))` allocates an S-expression in `arena`, an
+/// `Arena` or a `&mut Arena` evaluated once, and evaluates to its handle.
+/// Numbers such as `1/2`, `#t`/`#f` and symbols such as `set-car!` are
+/// written as in Lisp and read by the reader, as are dotted pairs and
+/// `'sym` for `(quote sym)`; strings and chars are Rust literals. `,x`, `,1` or `,(any + expr)`
+/// splices in a Rust value that implements `Interpolate`. A number the
+/// reader rejects, such as `1/0`, is a compile error.
+pub use risp_macros::risp;
+pub use symbol::{Interner, Keywords, SymbolId};
+pub use syntax::SyntaxRules;
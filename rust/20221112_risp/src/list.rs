@@ -0,0 +1,378 @@
+//! Iterators over arena lists, mirroring `Iter`/`IterMut`/`IntoIter` of a
+//! Rust collection. They yield car handles rather than strong cells, and
+//! report an improper tail, a circular list or a freed cell as a final `Err`
+//! instead of stopping quietly. `ListBuilder` goes the other way, from a
+//! Rust iterator to an arena list.
+
+use std::collections::HashSet;
+
+use crate::{
+    arena::{upgrade, Arena, RispExpRef, RispExpRefStrong},
+    error::{Result, RispError},
+    exp::RispExp,
+};
+
+/// The part every list iterator shares: the cdr to follow next and the
+/// cells already visited.
+#[derive(Default)]
+struct Walk {
+    rest: Option<RispExpRef>,
+    visited: HashSet<*const RispExp>,
+}
+
+impl Walk {
+    fn new(head: *const RispExp) -> Self {
+        Walk { rest: None, visited: HashSet::from([head]) }
+    }
+
+    /// Reads one cell, remembering its cdr. Returns `None` at the end of a
+    /// proper list and an error for any other tail.
+    fn step<'e>(&mut self, exp: &'e RispExp) -> Option<Result<&'e RispExpRef>> {
+        match exp {
+            RispExp::Cons{car, cdr} => {
+                self.rest = Some(cdr.clone());
+                Some(Ok(car))
+            },
+            RispExp::Nil => None,
+            tail => Some(Err(RispError::wrong_type("list", tail))),
+        }
+    }
+
+    /// The next cell to read, or `None` once the list ends.
+    fn next_cell(&mut self) -> Option<Result<RispExpRefStrong>> {
+        let rest = self.rest.take()?;
+        match upgrade(&rest) {
+            Ok(cell) if self.visited.insert(cell.as_ptr()) => Some(Ok(cell)),
+            Ok(_) => Some(Err(RispError::CircularList)),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+
+    fn next(&mut self) -> Option<Result<RispExpRef>> {
+        let cell = match self.next_cell()? {
+            Ok(cell) => cell,
+            Err(e) => return Some(Err(e)),
+        };
+        let exp = cell.borrow();
+        self.step(&exp).map(|car| car.cloned())
+    }
+}
+
+// IntoIter
+pub struct IntoIter {
+    head: Option<RispExp>,
+    walk: Walk,
+}
+
+impl Iterator for IntoIter {
+    type Item = Result<RispExpRef>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.head.take() {
+            Some(head) => self.walk.step(&head).map(|car| car.cloned()),
+            None => self.walk.next(),
+        }
+    }
+}
+
+/// Consumes a cell value. The value is detached from the arena, so a
+/// circular list is only noticed once it comes back to its second cell.
+impl IntoIterator for RispExp {
+    type Item = Result<RispExpRef>;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { head: Some(self), walk: Walk::default() }
+    }
+}
+
+// Iter
+pub struct Iter<'a> {
+    head: Option<&'a RispExp>,
+    walk: Walk,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Result<RispExpRef>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.head.take() {
+            Some(head) => self.walk.step(head).map(|car| car.cloned()),
+            None => self.walk.next(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a RispExp {
+    type Item = Result<RispExpRef>;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// IterMut
+/// One car slot of a list being iterated mutably.
+pub struct CarMut<'a>(CarSlot<'a>);
+
+enum CarSlot<'a> {
+    // the first cell is already borrowed by the iterator's caller
+    Head(&'a mut RispExpRef),
+    Cell(RispExpRefStrong),
+}
+
+impl CarMut<'_> {
+    pub fn get(&self) -> Result<RispExpRef> {
+        match &self.0 {
+            CarSlot::Head(car) => Ok((*car).clone()),
+            CarSlot::Cell(cell) => cell.borrow().car_weak(),
+        }
+    }
+
+    /// Points the car at `val`, as `set-car!` does.
+    pub fn set(&mut self, val: RispExpRef) -> Result<()> {
+        match &mut self.0 {
+            CarSlot::Head(car) => {
+                **car = val;
+                Ok(())
+            },
+            CarSlot::Cell(cell) => cell.borrow_mut().set_car(val),
+        }
+    }
+}
+
+pub struct IterMut<'a> {
+    head: Option<&'a mut RispExp>,
+    walk: Walk,
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = Result<CarMut<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(head) = self.head.take() {
+            return match head {
+                RispExp::Cons{car, cdr} => {
+                    self.walk.rest = Some(cdr.clone());
+                    Some(Ok(CarMut(CarSlot::Head(car))))
+                },
+                RispExp::Nil => None,
+                tail => Some(Err(RispError::wrong_type("list", tail))),
+            };
+        }
+        let cell = match self.walk.next_cell()? {
+            Ok(cell) => cell,
+            Err(e) => return Some(Err(e)),
+        };
+        let step = self.walk.step(&cell.borrow()).map(|car| car.map(|_| ()));
+        step.map(|step| step.map(|()| CarMut(CarSlot::Cell(cell))))
+    }
+}
+
+impl<'a> IntoIterator for &'a mut RispExp {
+    type Item = Result<CarMut<'a>>;
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl RispExp {
+    /// Iterates over the car handles of this list.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { head: Some(self), walk: Walk::new(self) }
+    }
+
+    /// Iterates over the car slots of this list, allowing each to be set.
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        let walk = Walk::new(self);
+        IterMut { head: Some(self), walk }
+    }
+}
+
+/// Builds a list from Rust iterators, as `FromIterator` and `Extend` build a
+/// collection. `FromIterator::from_iter` has no way to reach an arena, so
+/// the builder borrows one: `arena.list_builder()`, then `extend` or `push`,
+/// then `finish`.
+pub struct ListBuilder<'a> {
+    arena: &'a mut Arena,
+    items: Vec<RispExpRef>,
+}
+
+impl ListBuilder<'_> {
+    pub fn push(&mut self, item: RispExpRef) {
+        self.items.push(item);
+    }
+
+    /// Allocates the list, ended by `tail` instead of nil.
+    pub fn finish_with(self, tail: RispExpRef) -> RispExpRef {
+        let arena = self.arena;
+        self.items.into_iter().rev().fold(tail, |cdr, car| arena.alloc((car, cdr).into()))
+    }
+
+    /// Allocates the proper list of the items pushed so far.
+    pub fn finish(self) -> RispExpRef {
+        let nil = self.arena.nil();
+        self.finish_with(nil)
+    }
+}
+
+impl Extend<RispExpRef> for ListBuilder<'_> {
+    fn extend<I: IntoIterator<Item = RispExpRef>>(&mut self, iter: I) {
+        self.items.extend(iter);
+    }
+}
+
+impl<'r> Extend<&'r RispExpRef> for ListBuilder<'_> {
+    fn extend<I: IntoIterator<Item = &'r RispExpRef>>(&mut self, iter: I) {
+        self.items.extend(iter.into_iter().cloned());
+    }
+}
+
+impl Arena {
+    pub fn list_builder(&mut self) -> ListBuilder<'_> {
+        ListBuilder { arena: self, items: Vec::new() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval::list_to_vec, reader};
+
+    fn to_strings(items: impl Iterator<Item = Result<RispExpRef>>) -> Vec<String> {
+        items.map(|e| upgrade(&e.unwrap()).unwrap().borrow().to_string()).collect()
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut arena = Arena::new();
+        let lst = reader::read_one(&mut arena, "(1 (2 3) \"s\")").unwrap();
+        let lst = upgrade(&lst).unwrap();
+        assert_eq!(to_strings(lst.borrow().iter()), vec!["1", "(2 3)", "\"s\""]);
+
+        // for-in usage (implicit), leaving the list intact
+        let mut expected = vec!["\"s\"", "(2 3)", "1"];
+        for car in &*lst.borrow() {
+            assert_eq!(upgrade(&car.unwrap()).unwrap().borrow().to_string(), expected.pop().unwrap());
+        }
+        assert_eq!(lst.borrow().to_string(), "(1 (2 3) \"s\")");
+
+        assert_eq!(upgrade(&arena.nil()).unwrap().borrow().iter().count(), 0);
+    }
+
+    #[test]
+    fn test_improper() {
+        let mut arena = Arena::new();
+        let lst = reader::read_one(&mut arena, "(1 2 . 3)").unwrap();
+        let lst = upgrade(&lst).unwrap();
+        let lst = lst.borrow();
+        let mut iter = lst.iter();
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_ok());
+        assert_eq!(iter.next().unwrap().unwrap_err().to_string(), "wrong type: expected list, got int 3");
+        assert!(iter.next().is_none());
+
+        let atom = arena.alloc(5.into());
+        let err = upgrade(&atom).unwrap().borrow().iter().next().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "wrong type: expected list, got int 5");
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut arena = Arena::new();
+        let lst = reader::read_one(&mut arena, "(1 2)").unwrap();
+        let last = upgrade(&lst).unwrap().borrow().cdr_weak().unwrap();
+        upgrade(&last).unwrap().borrow_mut().set_cdr(lst.clone()).unwrap();
+        assert_eq!(crate::eval::list_to_vec(&lst).unwrap_err(), RispError::CircularList);
+        let lst = upgrade(&lst).unwrap();
+        // both cells are read once before the loop is reported
+        let head = lst.borrow();
+        let mut iter = head.iter().map(|car| car.map(|car| upgrade(&car).unwrap().borrow().to_string()));
+        assert_eq!(iter.next(), Some(Ok("1".to_string())));
+        assert_eq!(iter.next(), Some(Ok("2".to_string())));
+        assert_eq!(iter.next(), Some(Err(RispError::CircularList)));
+        assert_eq!(iter.next(), None);
+        drop(head);
+        let mut head = lst.borrow_mut();
+        assert!(matches!(head.iter_mut().nth(2), Some(Err(RispError::CircularList))));
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut arena = Arena::new();
+        let lst = reader::read_one(&mut arena, "(a b c)").unwrap();
+        let cell = upgrade(&lst).unwrap();
+        let copy: RispExp = (cell.borrow().car_weak().unwrap(), cell.borrow().cdr_weak().unwrap()).into();
+        assert_eq!(to_strings(RispExp::Nil.into_iter()), Vec::<String>::new());
+
+        let mut expected = vec!["c", "b", "a"];
+        for car in copy {
+            assert_eq!(upgrade(&car.unwrap()).unwrap().borrow().to_string(), expected.pop().unwrap());
+        }
+        assert!(expected.is_empty());
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut arena = Arena::new();
+        let a = reader::read_one(&mut arena, "(1 2 3)").unwrap();
+        // b shares a's cells, so it sees every set
+        let zero = arena.alloc(0.into());
+        let b = arena.alloc((&zero, &a).into());
+        let x = arena.symbol("x");
+
+        let cell = upgrade(&a).unwrap();
+        for car in &mut *cell.borrow_mut() {
+            let mut car = car.unwrap();
+            let is_odd = match &*upgrade(&car.get().unwrap()).unwrap().borrow() {
+                RispExp::Atom(atom) => *atom != 2.into(),
+                _ => false,
+            };
+            if is_odd {
+                car.set(x.clone()).unwrap();
+            }
+        }
+        assert_eq!(cell.borrow().to_string(), "(x 2 x)");
+        assert_eq!(upgrade(&b).unwrap().borrow().to_string(), "(0 x 2 x)");
+    }
+
+    #[test]
+    fn test_freed() {
+        let mut arena = Arena::new();
+        let lst = reader::read_one(&mut arena, "(1 2 3)").unwrap();
+        let lst_ptr = upgrade(&lst).unwrap();
+        arena.collect([]);
+
+        // the head's own car handle is returned as is; following it fails later
+        let lst = lst_ptr.borrow();
+        let mut iter = lst.iter();
+        assert!(upgrade(&iter.next().unwrap().unwrap()).is_err());
+        assert_eq!(iter.next().unwrap().unwrap_err(), RispError::DanglingRef);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_list_builder() -> anyhow::Result<()> {
+        let mut arena = Arena::new();
+        let src = reader::read_one(&mut arena, "(1 2 3)")?;
+        let items = list_to_vec(&src)?;
+
+        // a round trip through a Rust iterator, filtered on the way
+        let mut builder = arena.list_builder();
+        builder.extend(items.iter().filter(|item| !upgrade(item).unwrap().borrow().to_string().starts_with('2')));
+        let lst = builder.finish();
+        assert_eq!(upgrade(&lst)?.borrow().to_string(), "(1 3)");
+
+        let mut builder = arena.list_builder();
+        builder.push(items[0].clone());
+        builder.extend(items[1..].iter().cloned());
+        let tail = items[2].clone();
+        assert_eq!(upgrade(&builder.finish_with(tail))?.borrow().to_string(), "(1 2 3 . 3)");
+
+        assert!(arena.list_builder().finish().ptr_eq(&arena.nil()));
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+
+use risp::Repl;
+use rustyline::{error::ReadlineError, Editor};
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".risp_history"))
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut rl = Editor::<()>::new()?;
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = rl.load_history(path);
+    }
+
+    let mut repl = Repl::new();
+    loop {
+        let prompt = if repl.is_continuing() { "...   " } else { "risp> " };
+        match rl.readline(prompt) {
+            Ok(line) => {
+                let pending = format!("{}\n{}", repl.pending(), line);
+                let Some(results) = repl.feed(&line) else { continue };
+                rl.add_history_entry(pending.trim());
+                for result in results {
+                    match result {
+                        Ok(s) => println!("{}", s),
+                        Err(e) => eprintln!("error: {}", e),
+                    }
+                }
+            },
+            Err(ReadlineError::Interrupted) => repl.cancel(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if let Some(path) = &history {
+        rl.save_history(path)?;
+    }
+    Ok(())
+}
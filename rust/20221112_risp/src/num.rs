@@ -0,0 +1,258 @@
+//! Numeric tower. Fixnums promote to bignums on overflow, exact division
+//! yields rationals, and a float operand makes the whole result inexact.
+//! Exact results are always normalized back to the smallest representation.
+
+use std::cmp::Ordering;
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::{
+    error::{Result, RispError},
+    exp::RispAtom,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl RispAtom {
+    pub fn is_number(&self) -> bool {
+        matches!(self, RispAtom::Int(_) | RispAtom::BigInt(_) | RispAtom::Rational(_) | RispAtom::Float(_))
+    }
+
+    pub fn is_exact(&self) -> bool {
+        matches!(self, RispAtom::Int(_) | RispAtom::BigInt(_) | RispAtom::Rational(_))
+    }
+
+    fn to_big(&self) -> BigInt {
+        match self {
+            RispAtom::Int(i) => BigInt::from(*i),
+            RispAtom::BigInt(i) => i.clone(),
+            _ => unreachable!("not an integer: {}", self),
+        }
+    }
+
+    fn to_ratio(&self) -> BigRational {
+        match self {
+            RispAtom::Rational(r) => r.clone(),
+            _ => BigRational::from_integer(self.to_big()),
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        match self {
+            RispAtom::Int(i) => *i as f64,
+            RispAtom::BigInt(i) => i.to_f64().unwrap_or(f64::NAN),
+            RispAtom::Rational(r) => r.to_f64().unwrap_or(f64::NAN),
+            RispAtom::Float(f) => *f,
+            _ => unreachable!("not a number: {}", self),
+        }
+    }
+
+    /// The float closest to this number.
+    pub fn to_inexact(&self) -> RispAtom {
+        RispAtom::Float(self.to_f64())
+    }
+
+    /// The exact number equal to this one; infinities and NaN have none.
+    pub fn to_exact(&self) -> Result<RispAtom> {
+        match self {
+            RispAtom::Float(f) => BigRational::from_float(*f)
+                .map(RispAtom::from)
+                .ok_or(RispError::Arithmetic("no exact representation")),
+            _ => Ok(self.clone()),
+        }
+    }
+}
+
+fn rank(n: &RispAtom) -> u8 {
+    match n {
+        RispAtom::Int(_) => 0,
+        RispAtom::BigInt(_) => 1,
+        RispAtom::Rational(_) => 2,
+        RispAtom::Float(_) => 3,
+        _ => unreachable!("not a number: {}", n),
+    }
+}
+
+/// Applies `op` to two numbers at the level of the more general operand.
+pub(crate) fn arith(op: Op, a: &RispAtom, b: &RispAtom) -> Result<RispAtom> {
+    if let (RispAtom::Int(x), RispAtom::Int(y), false) = (a, b, op == Op::Div) {
+        let result = match op {
+            Op::Add => x.checked_add(*y),
+            Op::Sub => x.checked_sub(*y),
+            Op::Mul => x.checked_mul(*y),
+            Op::Div => unreachable!(),
+        };
+        if let Some(i) = result {
+            return Ok(RispAtom::Int(i));
+        }
+    }
+
+    let level = rank(a).max(rank(b));
+    if level == 3 {
+        let (x, y) = (a.to_f64(), b.to_f64());
+        return Ok(RispAtom::Float(match op {
+            Op::Add => x + y,
+            Op::Sub => x - y,
+            Op::Mul => x * y,
+            Op::Div => x / y,
+        }));
+    }
+    if level == 2 || op == Op::Div {
+        let (x, y) = (a.to_ratio(), b.to_ratio());
+        return Ok(match op {
+            Op::Add => x + y,
+            Op::Sub => x - y,
+            Op::Mul => x * y,
+            Op::Div if y.is_zero() => return Err(RispError::Arithmetic("division by zero")),
+            Op::Div => x / y,
+        }
+        .into());
+    }
+    let (x, y) = (a.to_big(), b.to_big());
+    Ok(match op {
+        Op::Add => x + y,
+        Op::Sub => x - y,
+        Op::Mul => x * y,
+        Op::Div => unreachable!(),
+    }
+    .into())
+}
+
+/// Numeric comparison across representations; `None` if either is NaN.
+pub(crate) fn compare(a: &RispAtom, b: &RispAtom) -> Option<Ordering> {
+    match (a, b) {
+        (RispAtom::Int(x), RispAtom::Int(y)) => Some(x.cmp(y)),
+        _ if rank(a) == 3 || rank(b) == 3 => a.to_f64().partial_cmp(&b.to_f64()),
+        _ => Some(a.to_ratio().cmp(&b.to_ratio())),
+    }
+}
+
+pub(crate) fn fmt_float(f: &mut std::fmt::Formatter<'_>, x: f64) -> std::fmt::Result {
+    if x.is_nan() {
+        f.write_str("+nan.0")
+    } else if x.is_infinite() {
+        f.write_str(if x > 0.0 { "+inf.0" } else { "-inf.0" })
+    } else {
+        // Debug writes 1.0 as "1.0" but 1e44 as "1e44"; give every float a
+        // fraction so none looks like an integer
+        let text = format!("{:?}", x);
+        match text.split_once('e') {
+            Some((mantissa, exp)) if !mantissa.contains('.') => write!(f, "{}.0e{}", mantissa, exp),
+            _ => f.write_str(&text),
+        }
+    }
+}
+
+/// Parses integer, `n/d` rational and float literals. Returns `Ok(None)`
+/// for text that is not a number at all, and `Err` for a rational with a
+/// zero denominator.
+pub(crate) fn parse(text: &str) -> std::result::Result<Option<RispAtom>, ()> {
+    match text {
+        "+inf.0" => return Ok(Some(RispAtom::Float(f64::INFINITY))),
+        "-inf.0" => return Ok(Some(RispAtom::Float(f64::NEG_INFINITY))),
+        "+nan.0" | "-nan.0" => return Ok(Some(RispAtom::Float(f64::NAN))),
+        _ => (),
+    }
+    if is_integer(text) {
+        return Ok(text.parse::<BigInt>().ok().map(RispAtom::from));
+    }
+    if let Some((numer, denom)) = text.split_once('/') {
+        if !is_integer(numer) || denom.is_empty() || !denom.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(None);
+        }
+        let (numer, denom) = (numer.parse::<BigInt>().unwrap(), denom.parse::<BigInt>().unwrap());
+        if denom.is_zero() {
+            return Err(());
+        }
+        return Ok(Some(BigRational::new(numer, denom).into()));
+    }
+    // f64::from_str also accepts words like "inf" and "nan", which are symbols here
+    let float_chars = text.chars().all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E'));
+    Ok(text.parse::<f64>().ok().filter(|_| float_chars).map(RispAtom::Float))
+}
+
+fn is_integer(text: &str) -> bool {
+    let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(text: &str) -> RispAtom {
+        parse(text).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(num("42"), RispAtom::Int(42));
+        assert_eq!(num("-7"), RispAtom::Int(-7));
+        assert!(matches!(num("99999999999999999999"), RispAtom::BigInt(_)));
+        assert_eq!(num("6/4").to_string(), "3/2");
+        assert_eq!(num("4/2"), RispAtom::Int(2));
+        assert_eq!(num("1.5"), RispAtom::Float(1.5));
+        assert_eq!(num("1e3"), RispAtom::Float(1000.0));
+        assert_eq!(num("-inf.0"), RispAtom::Float(f64::NEG_INFINITY));
+        assert_eq!(num("-.5"), RispAtom::Float(-0.5));
+        assert_eq!(parse("1/0"), Err(()));
+        assert_eq!(parse("1x"), Ok(None));
+        assert_eq!(parse("-"), Ok(None));
+        assert_eq!(parse("inf"), Ok(None));
+        assert_eq!(parse("1/"), Ok(None));
+    }
+
+    #[test]
+    fn test_promotion() {
+        let big = arith(Op::Mul, &num("99999999999"), &num("99999999999")).unwrap();
+        assert_eq!(big.to_string(), "9999999999800000000001");
+        let max = RispAtom::Int(i64::MAX);
+        let sum = arith(Op::Add, &max, &1.into()).unwrap();
+        assert!(matches!(sum, RispAtom::BigInt(_)));
+        assert_eq!(arith(Op::Sub, &sum, &1.into()).unwrap(), max);
+
+        assert_eq!(arith(Op::Div, &1.into(), &3.into()).unwrap().to_string(), "1/3");
+        assert_eq!(arith(Op::Div, &6.into(), &3.into()).unwrap(), RispAtom::Int(2));
+        assert_eq!(arith(Op::Add, &num("1/2"), &num("1/2")).unwrap(), RispAtom::Int(1));
+        assert_eq!(arith(Op::Add, &num("1/2"), &1.5.into()).unwrap(), RispAtom::Float(2.0));
+        assert_eq!(arith(Op::Div, &1.into(), &0.into()), Err(RispError::Arithmetic("division by zero")));
+        assert_eq!(arith(Op::Div, &1.0.into(), &0.into()).unwrap(), RispAtom::Float(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(compare(&1.into(), &num("3/2")), Some(Ordering::Less));
+        assert_eq!(compare(&num("3/2"), &1.5.into()), Some(Ordering::Equal));
+        assert_eq!(compare(&num("99999999999999999999"), &i64::MAX.into()), Some(Ordering::Greater));
+        assert_eq!(compare(&f64::NAN.into(), &1.into()), None);
+    }
+
+    #[test]
+    fn test_exactness() {
+        assert_eq!(num("1/4").to_inexact(), RispAtom::Float(0.25));
+        assert_eq!(RispAtom::Float(0.25).to_exact().unwrap().to_string(), "1/4");
+        assert_eq!(RispAtom::Float(3.0).to_exact().unwrap(), RispAtom::Int(3));
+        assert!(RispAtom::Float(f64::NAN).to_exact().is_err());
+        assert!(num("1/3").is_exact());
+        assert!(!num("1.0").is_exact());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(RispAtom::Float(1.0).to_string(), "1.0");
+        assert_eq!(RispAtom::Float(-0.5).to_string(), "-0.5");
+        assert_eq!(RispAtom::Float(f64::INFINITY).to_string(), "+inf.0");
+        assert_eq!(RispAtom::Float(1e44).to_string(), "1.0e44");
+        assert_eq!(RispAtom::Float(-2.5e-9).to_string(), "-2.5e-9");
+        assert_eq!(num(&RispAtom::Float(1e44).to_string()), RispAtom::Float(1e44));
+        assert_eq!(num("-2/6").to_string(), "-1/3");
+    }
+}
@@ -0,0 +1,213 @@
+use std::{collections::{HashMap, HashSet}, fmt::{Display, Formatter, Result}};
+
+use crate::{arena::{upgrade, RispExpRef}, exp::RispExp};
+
+type CellId = *const RispExp;
+
+const FREED: &str = "#<freed>";
+
+/// Prints cons graphs in list notation, labelling every cell reachable more
+/// than once as `#n=` on first occurrence and `#n#` afterwards.
+struct Printer {
+    labels: HashMap<CellId, Option<usize>>,
+    next_label: usize,
+}
+
+impl Printer {
+    fn new(root: &RispExp) -> Self {
+        Printer { labels: find_shared(root), next_label: 0 }
+    }
+
+    fn write_exp(&mut self, f: &mut Formatter<'_>, exp: &RispExp, id: CellId) -> Result {
+        let (car, cdr) = match exp {
+            RispExp::Nil => return write!(f, "nil"),
+            RispExp::Atom(a) => return write!(f, "{}", a),
+            RispExp::Builtin(b) => return write!(f, "#<builtin {}>", b.name),
+            RispExp::Lambda(_) | RispExp::Closure(_) => return write!(f, "#<lambda>"),
+            RispExp::Macro(_) => return write!(f, "#<macro>"),
+            RispExp::Syntax(_) => return write!(f, "#<syntax>"),
+            RispExp::Continuation(_) => return write!(f, "#<continuation>"),
+            RispExp::Cons{car, cdr} => (car, cdr),
+        };
+
+        if let Some(label) = self.labels.get_mut(&id) {
+            match label {
+                Some(n) => return write!(f, "#{}#", n),
+                None => {
+                    *label = Some(self.next_label);
+                    write!(f, "#{}=", self.next_label)?;
+                    self.next_label += 1;
+                },
+            }
+        }
+
+        write!(f, "(")?;
+        self.write_ref(f, car)?;
+        let mut rest = cdr.clone();
+        loop {
+            let Ok(rest_ptr) = upgrade(&rest) else {
+                write!(f, " . {}", FREED)?;
+                break;
+            };
+            let next = match &*rest_ptr.borrow() {
+                RispExp::Cons{car, cdr} if !self.labels.contains_key(&rest_ptr.as_ptr().cast_const()) => {
+                    write!(f, " ")?;
+                    self.write_ref(f, car)?;
+                    cdr.clone()
+                },
+                exp if exp.is_nil() => break,
+                exp => {
+                    write!(f, " . ")?;
+                    self.write_exp(f, exp, rest_ptr.as_ptr())?;
+                    break;
+                },
+            };
+            rest = next;
+        }
+        write!(f, ")")
+    }
+
+    fn write_ref(&mut self, f: &mut Formatter<'_>, exp: &RispExpRef) -> Result {
+        match upgrade(exp) {
+            Ok(exp) => self.write_exp(f, &exp.borrow(), exp.as_ptr()),
+            Err(_) => write!(f, "{}", FREED),
+        }
+    }
+}
+
+fn find_shared(root: &RispExp) -> HashMap<CellId, Option<usize>> {
+    let mut seen = HashSet::from([root as CellId]);
+    let mut shared = HashMap::new();
+    let mut stack = Vec::new();
+    if let RispExp::Cons{car, cdr} = root {
+        stack.push(cdr.clone());
+        stack.push(car.clone());
+    }
+    while let Some(exp) = stack.pop() {
+        let Ok(exp_ptr) = upgrade(&exp) else { continue };
+        let exp = exp_ptr.borrow();
+        if let RispExp::Cons{car, cdr} = &*exp {
+            let id = exp_ptr.as_ptr().cast_const();
+            if !seen.insert(id) {
+                shared.insert(id, None);
+                continue;
+            }
+            stack.push(cdr.clone());
+            stack.push(car.clone());
+        }
+    }
+    shared
+}
+
+impl Display for RispExp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        Printer::new(self).write_exp(f, self, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{alloc, arena::{upgrade, Arena, DanglingRef}, error::RispError};
+
+    #[test]
+    fn test_display() {
+        let mut arena = Arena::new();
+        let nil = arena.nil();
+        let v1 = arena.alloc(1.into());
+        let v2 = arena.alloc(2.into());
+        let v3 = arena.alloc(3.into());
+
+        let exp = alloc!(arena, [v1]);
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "(1)");
+
+        let exp = alloc!(arena, [v1, v2]);
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "(1 . 2)");
+
+        let exp = alloc!(arena, [v1, v2, v3]);
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "(1 2 . 3)");
+
+        let exp = alloc!(arena, [v1, v2, v3, nil]);
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "(1 2 3)");
+
+        let exp = alloc!(arena, [[v1, v2, nil], [v3]]);
+        assert_eq!(exp.upgrade().unwrap().borrow().to_string(), "((1 2) 3)");
+
+        assert_eq!(nil.upgrade().unwrap().borrow().to_string(), "nil");
+    }
+
+    #[test]
+    fn test_display_shared() {
+        let mut arena = Arena::new();
+        let nil = arena.nil();
+        let v1 = arena.alloc(1.into());
+        let v2 = arena.alloc(2.into());
+
+        let a = alloc!(arena, [v1, v2, nil]);
+        let b = alloc!(arena, [a, a, nil]);
+        assert_eq!(b.upgrade().unwrap().borrow().to_string(), "(#0=(1 2) #0#)");
+
+        // shared tail
+        let c = arena.alloc((&a, &a).into());
+        assert_eq!(c.upgrade().unwrap().borrow().to_string(), "(#0=(1 2) . #0#)");
+
+        // shared atoms are not labelled
+        let d = alloc!(arena, [v1, v1, nil]);
+        assert_eq!(d.upgrade().unwrap().borrow().to_string(), "(1 1)");
+    }
+
+    #[test]
+    fn test_display_cycle() -> anyhow::Result<()> {
+        let mut arena = Arena::new();
+        let nil = arena.nil();
+        let v1 = arena.alloc(1.into());
+        let v2 = arena.alloc(2.into());
+        let v3 = arena.alloc(3.into());
+
+        // (1 2 3 1 2 3 ...)
+        let lst = alloc!(arena, [v1, v2, v3, nil]);
+        let last = lst.upgrade().unwrap().borrow().cdr()?.borrow().cdr_weak()?;
+        last.upgrade().unwrap().borrow_mut().set_cdr(lst.clone())?;
+        assert_eq!(lst.upgrade().unwrap().borrow().to_string(), "#0=(1 2 3 . #0#)");
+
+        // iterating reads each cell once, then reports the cycle
+        let items: Vec<_> = lst.upgrade().unwrap().borrow().iter().collect();
+        let values: Vec<_> = items[..3].iter().map(|e| upgrade(e.as_ref().unwrap()).unwrap().borrow().to_string()).collect();
+        assert_eq!(values, vec!["1", "2", "3"]);
+        assert!(matches!(items[3..], [Err(RispError::CircularList)]));
+
+        // car pointing back to its own cell
+        let x = arena.alloc((&v1, &nil).into());
+        x.upgrade().unwrap().borrow_mut().set_car(x.clone())?;
+        assert_eq!(x.upgrade().unwrap().borrow().to_string(), "#0=(#0#)");
+
+        let y = alloc!(arena, [v1, x, nil]);
+        assert_eq!(y.upgrade().unwrap().borrow().to_string(), "(1 #0=(#0#))");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_freed() -> anyhow::Result<()> {
+        let mut arena = Arena::new();
+        let nil = arena.nil();
+        let v1 = arena.alloc(1.into());
+        let v2 = arena.alloc(2.into());
+        let v3 = arena.alloc(3.into());
+        let tail = alloc!(arena, [v3, nil]);
+        let lst = alloc!(arena, [v1, v2, tail]);
+        let lst_ptr = lst.upgrade().unwrap();
+
+        arena.collect([]);
+        assert_eq!(lst_ptr.borrow().to_string(), "(#<freed> . #<freed>)");
+        assert_eq!(lst_ptr.borrow().car().unwrap_err(), RispError::DanglingRef);
+        assert_eq!(lst_ptr.borrow().cdr().unwrap_err(), RispError::DanglingRef);
+
+        let lst = lst_ptr.borrow();
+        let mut iter = lst.iter();
+        assert_eq!(upgrade(&iter.next().unwrap()?).unwrap_err(), DanglingRef);
+        assert_eq!(iter.next().unwrap().unwrap_err(), RispError::DanglingRef);
+        assert!(iter.next().is_none());
+
+        Ok(())
+    }
+}
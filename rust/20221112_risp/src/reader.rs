@@ -0,0 +1,439 @@
+use std::{fmt::Display, iter::Peekable, str::Chars};
+
+use crate::{
+    arena::{Arena, RispExpRef},
+    exp::{char_named, RispAtom, RispExp},
+    num,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Display for Pos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnexpectedEof,
+    UnclosedList,
+    UnexpectedCloseParen,
+    UnexpectedDot,
+    ExpectedCloseParen,
+    ExpectedEof,
+    InvalidNumber(String),
+    UnclosedString,
+    InvalidEscape(char),
+    UnknownChar(String),
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedEof => write!(f, "unexpected end of input"),
+            ParseErrorKind::UnclosedList => write!(f, "unclosed '('"),
+            ParseErrorKind::UnexpectedCloseParen => write!(f, "unexpected ')'"),
+            ParseErrorKind::UnexpectedDot => write!(f, "unexpected '.'"),
+            ParseErrorKind::ExpectedCloseParen => write!(f, "expected ')' after dotted tail"),
+            ParseErrorKind::ExpectedEof => write!(f, "expected a single expression"),
+            ParseErrorKind::InvalidNumber(s) => write!(f, "invalid number: {}", s),
+            ParseErrorKind::UnclosedString => write!(f, "unclosed '\"'"),
+            ParseErrorKind::InvalidEscape(c) => write!(f, "invalid escape: \\{}", c),
+            ParseErrorKind::UnknownChar(name) => write!(f, "unknown character: #\\{}", name),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub pos: Pos,
+}
+
+impl ParseError {
+    fn new(kind: ParseErrorKind, pos: Pos) -> Self {
+        ParseError { kind, pos }
+    }
+
+    /// True when more input could still turn the source into a valid expression.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(
+            self.kind,
+            ParseErrorKind::UnexpectedEof | ParseErrorKind::UnclosedList | ParseErrorKind::UnclosedString
+        )
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.pos, self.kind)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Dot,
+    /// `'`, `` ` ``, `,` or `,@`, holding the form it abbreviates.
+    Quote(&'static str),
+    Literal(RispAtom),
+    Symbol(String),
+}
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    pos: Pos,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a str) -> Self {
+        Lexer { chars: src.chars().peekable(), pos: Pos { line: 1, col: 1 } }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.col = 1;
+        } else {
+            self.pos.col += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c == ';' {
+                while !matches!(self.bump(), Some('\n') | None) {}
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<(Token, Pos)>, ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let token = match self.chars.peek() {
+            None => return Ok(None),
+            Some('(') => { self.bump(); Token::LParen },
+            Some(')') => { self.bump(); Token::RParen },
+            Some('\'') => { self.bump(); Token::Quote("quote") },
+            Some('`') => { self.bump(); Token::Quote("quasiquote") },
+            Some(',') => {
+                self.bump();
+                if self.chars.peek() == Some(&'@') {
+                    self.bump();
+                    Token::Quote("unquote-splicing")
+                } else {
+                    Token::Quote("unquote")
+                }
+            },
+            Some('"') => { self.bump(); self.string(start)? },
+            Some(_) => {
+                let text = self.take_while(|c| !is_delimiter(c));
+                match text.strip_prefix("#\\") {
+                    Some(name) => self.char(name, start)?,
+                    None => atom_token(text, start)?,
+                }
+            },
+        };
+        Ok(Some((token, start)))
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let mut text = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !pred(c) {
+                break;
+            }
+            text.push(c);
+            self.bump();
+        }
+        text
+    }
+
+    /// Lexes the rest of a string literal after its opening quote.
+    fn string(&mut self, start: Pos) -> Result<Token, ParseError> {
+        let mut s = String::new();
+        loop {
+            let c = match self.bump() {
+                None => return Err(ParseError::new(ParseErrorKind::UnclosedString, start)),
+                Some('"') => return Ok(Token::Literal(RispAtom::Str(s))),
+                Some(c) => c,
+            };
+            if c != '\\' {
+                s.push(c);
+                continue;
+            }
+            let pos = self.pos;
+            s.push(match self.bump() {
+                None => return Err(ParseError::new(ParseErrorKind::UnclosedString, start)),
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('0') => '\0',
+                Some(c @ ('"' | '\\')) => c,
+                Some(c) => return Err(ParseError::new(ParseErrorKind::InvalidEscape(c), pos)),
+            });
+        }
+    }
+
+    /// Lexes a character literal from the text after its `#\` prefix.
+    /// An empty name means the character is itself a delimiter, as in `#\(`.
+    fn char(&mut self, name: &str, start: Pos) -> Result<Token, ParseError> {
+        let mut chars = name.chars();
+        let c = match (chars.next(), chars.next()) {
+            (None, _) => self.bump().ok_or(ParseError::new(ParseErrorKind::UnexpectedEof, self.pos))?,
+            (Some(c), None) => c,
+            _ => char_named(name).ok_or_else(|| {
+                ParseError::new(ParseErrorKind::UnknownChar(name.to_string()), start)
+            })?,
+        };
+        Ok(Token::Literal(RispAtom::Char(c)))
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '\'' | '`' | ',' | ';' | '"')
+}
+
+fn atom_token(text: String, pos: Pos) -> Result<Token, ParseError> {
+    match text.as_str() {
+        "." => return Ok(Token::Dot),
+        "#t" | "#true" => return Ok(Token::Literal(RispAtom::Bool(true))),
+        "#f" | "#false" => return Ok(Token::Literal(RispAtom::Bool(false))),
+        _ => (),
+    }
+    match num::parse(&text) {
+        Ok(Some(n)) => return Ok(Token::Literal(n)),
+        Ok(None) => (),
+        Err(()) => return Err(ParseError::new(ParseErrorKind::InvalidNumber(text), pos)),
+    }
+    Ok(Token::Symbol(text))
+}
+
+/// Reads S-expressions from source text into arena-allocated cells.
+pub struct Reader<'a> {
+    lexer: Lexer<'a>,
+    peeked: Option<(Token, Pos)>,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Reader { lexer: Lexer::new(src), peeked: None }
+    }
+
+    fn next_token(&mut self) -> Result<Option<(Token, Pos)>, ParseError> {
+        match self.peeked.take() {
+            Some(t) => Ok(Some(t)),
+            None => self.lexer.next_token(),
+        }
+    }
+
+    fn peek_token(&mut self) -> Result<Option<&(Token, Pos)>, ParseError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.next_token()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    /// Reads the next expression, or `None` once the input is exhausted.
+    pub fn read(&mut self, arena: &mut Arena) -> Result<Option<RispExpRef>, ParseError> {
+        match self.next_token()? {
+            None => Ok(None),
+            Some((token, pos)) => self.read_exp(arena, token, pos).map(Some),
+        }
+    }
+
+    fn read_required(&mut self, arena: &mut Arena) -> Result<RispExpRef, ParseError> {
+        match self.next_token()? {
+            None => Err(ParseError::new(ParseErrorKind::UnexpectedEof, self.lexer.pos)),
+            Some((token, pos)) => self.read_exp(arena, token, pos),
+        }
+    }
+
+    fn read_exp(&mut self, arena: &mut Arena, token: Token, pos: Pos) -> Result<RispExpRef, ParseError> {
+        match token {
+            Token::Literal(atom) => Ok(arena.alloc(RispExp::Atom(atom))),
+            Token::Symbol(s) => Ok(arena.symbol(&s)),
+            Token::Quote(name) => {
+                let exp = self.read_required(arena)?;
+                let quote = arena.symbol(name);
+                let nil = arena.nil();
+                let rest = arena.alloc((exp, nil).into());
+                Ok(arena.alloc((quote, rest).into()))
+            },
+            Token::LParen => self.read_list(arena, pos),
+            Token::RParen => Err(ParseError::new(ParseErrorKind::UnexpectedCloseParen, pos)),
+            Token::Dot => Err(ParseError::new(ParseErrorKind::UnexpectedDot, pos)),
+        }
+    }
+
+    fn read_list(&mut self, arena: &mut Arena, open: Pos) -> Result<RispExpRef, ParseError> {
+        let mut items = Vec::new();
+        let tail = loop {
+            let (token, pos) = match self.next_token()? {
+                None => return Err(ParseError::new(ParseErrorKind::UnclosedList, open)),
+                Some(t) => t,
+            };
+            match token {
+                Token::RParen => break arena.nil(),
+                Token::Dot if items.is_empty() => {
+                    return Err(ParseError::new(ParseErrorKind::UnexpectedDot, pos));
+                },
+                Token::Dot => {
+                    let tail = self.read_required(arena).map_err(|e| match e.kind {
+                        ParseErrorKind::UnexpectedEof => ParseError::new(ParseErrorKind::UnclosedList, open),
+                        _ => e,
+                    })?;
+                    match self.peek_token()? {
+                        Some((Token::RParen, _)) => { self.next_token()?; },
+                        Some((_, pos)) => return Err(ParseError::new(ParseErrorKind::ExpectedCloseParen, *pos)),
+                        None => return Err(ParseError::new(ParseErrorKind::UnclosedList, open)),
+                    }
+                    break tail;
+                },
+                token => items.push(self.read_exp(arena, token, pos)?),
+            }
+        };
+        Ok(items.into_iter().rev().fold(tail, |cdr, car| arena.alloc((car, cdr).into())))
+    }
+}
+
+/// Reads every expression in `src`.
+pub fn read(arena: &mut Arena, src: &str) -> Result<Vec<RispExpRef>, ParseError> {
+    let mut reader = Reader::new(src);
+    let mut exps = Vec::new();
+    while let Some(exp) = reader.read(arena)? {
+        exps.push(exp);
+    }
+    Ok(exps)
+}
+
+/// Reads exactly one expression from `src`.
+pub fn read_one(arena: &mut Arena, src: &str) -> Result<RispExpRef, ParseError> {
+    let mut reader = Reader::new(src);
+    let exp = reader.read_required(arena)?;
+    if let Some((_, pos)) = reader.next_token()? {
+        return Err(ParseError::new(ParseErrorKind::ExpectedEof, pos));
+    }
+    Ok(exp)
+}
+
+/// Reads `text` as a single atom: a boolean, number or symbol. Unlike
+/// `read_one`, the text is not tokenized, so it may contain delimiters.
+pub fn read_atom(arena: &mut Arena, text: &str) -> Result<RispExpRef, ParseError> {
+    let pos = Pos { line: 1, col: 1 };
+    let token = atom_token(text.to_string(), pos)?;
+    Reader::new("").read_exp(arena, token, pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_str(src: &str) -> String {
+        let mut arena = Arena::new();
+        let exp = read_one(&mut arena, src).unwrap();
+        let s = exp.upgrade().unwrap().borrow().to_string();
+        s
+    }
+
+    fn read_err(src: &str) -> ParseError {
+        let mut arena = Arena::new();
+        read(&mut arena, src).unwrap_err()
+    }
+
+    #[test]
+    fn test_atoms() {
+        assert_eq!(read_str("42"), "42");
+        assert_eq!(read_str("-7"), "-7");
+        assert_eq!(read_str("+3"), "3");
+        assert_eq!(read_str("foo"), "foo");
+        assert_eq!(read_str("-"), "-");
+        assert_eq!(read_str("1+"), "1+");
+        assert_eq!(read_str("99999999999999999999"), "99999999999999999999");
+        assert_eq!(read_str("-3/6"), "-1/2");
+        assert_eq!(read_str("2.50"), "2.5");
+        assert_eq!(read_str("+inf.0"), "+inf.0");
+        assert_eq!(read_str("nan"), "nan");
+        assert_eq!(read_str("#t"), "#t");
+        assert_eq!(read_str("#false"), "#f");
+        assert_eq!(read_str(r#""a b""#), r#""a b""#);
+        assert_eq!(read_str(r#""say \"hi\"\n\ttab\\""#), r#""say \"hi\"\n\ttab\\""#);
+        assert_eq!(read_str(r"#\a"), r"#\a");
+        assert_eq!(read_str(r"#\space"), r"#\space");
+        assert_eq!(read_str(r"#\("), r"#\(");
+        assert_eq!(read_str(r"#\ "), r"#\space");
+        assert_eq!(read_str(r"#\λ"), r"#\λ");
+        assert_eq!(read_str(r##"("a"#\b)"##), r##"("a" #\b)"##);
+    }
+
+    #[test]
+    fn test_list() {
+        assert_eq!(read_str("(1 2 3)"), "(1 2 3)");
+        assert_eq!(read_str("()"), "nil");
+        assert_eq!(read_str("(ldc (quote a))"), "(ldc (quote a))");
+    }
+
+    #[test]
+    fn test_dotted() {
+        assert_eq!(read_str("(1 . 2)"), "(1 . 2)");
+        assert_eq!(read_str("(1 2 . 3)"), "(1 2 . 3)");
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(read_str("'a"), "(quote a)");
+        assert_eq!(read_str("'(1 . 2)"), "(quote (1 . 2))");
+        assert_eq!(read_str("`(a ,b ,@c)"), "(quasiquote (a (unquote b) (unquote-splicing c)))");
+        assert_eq!(read_str("`(a . ,b)"), "(quasiquote (a unquote b))");
+        assert_eq!(read_str("(a,b)"), "(a (unquote b))");
+        assert_eq!(read_str(", @a"), "(unquote @a)");
+        assert_eq!(read_err("`").kind, ParseErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_multiple() {
+        let mut arena = Arena::new();
+        let exps = read(&mut arena, "1 ; comment\n(a b)\n'c").unwrap();
+        let exps: Vec<_> = exps.iter().map(|e| e.upgrade().unwrap().borrow().to_string()).collect();
+        assert_eq!(exps, vec!["1", "(a b)", "(quote c)"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = read_err("(1 2");
+        assert_eq!(err, ParseError::new(ParseErrorKind::UnclosedList, Pos { line: 1, col: 1 }));
+        assert!(err.is_incomplete());
+
+        let err = read_err("(a\n  b))");
+        assert_eq!(err, ParseError::new(ParseErrorKind::UnexpectedCloseParen, Pos { line: 2, col: 5 }));
+        assert_eq!(err.to_string(), "2:5: unexpected ')'");
+        assert!(!err.is_incomplete());
+
+        assert_eq!(read_err("( . 1)").kind, ParseErrorKind::UnexpectedDot);
+        assert_eq!(read_err("(1 . 2 3)").pos, Pos { line: 1, col: 8 });
+        assert!(read_err("'").is_incomplete());
+        assert_eq!(read_err("1/0").kind, ParseErrorKind::InvalidNumber("1/0".to_string()));
+        assert_eq!(read_err("\"abc").kind, ParseErrorKind::UnclosedString);
+        assert!(read_err("(\"abc)").is_incomplete());
+        assert_eq!(read_err(r#""a\qb""#), ParseError::new(ParseErrorKind::InvalidEscape('q'), Pos { line: 1, col: 4 }));
+        assert_eq!(read_err(r"#\bogus").to_string(), r"1:1: unknown character: #\bogus");
+        assert_eq!(read_err("(1 2/0)").to_string(), "1:4: invalid number: 2/0");
+
+        let mut arena = Arena::new();
+        assert_eq!(read_one(&mut arena, "1 2").unwrap_err().kind, ParseErrorKind::ExpectedEof);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    arena::{upgrade, Arena, RispExpRef},
+    env::Env,
+    error::Result,
+    eval::{eval, global_env},
+    reader,
+};
+
+/// Line-oriented front end of the interpreter. Input is buffered until it
+/// holds complete expressions, so a form can span several lines.
+pub struct Repl {
+    arena: Arena,
+    env: Env,
+    buf: String,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        let mut arena = Arena::new();
+        let env = global_env(&mut arena);
+        Repl { arena, env, buf: String::new() }
+    }
+
+    /// True while an unfinished expression is waiting for more lines.
+    pub fn is_continuing(&self) -> bool {
+        !self.buf.is_empty()
+    }
+
+    /// The buffered input, for recording in the line editor history.
+    pub fn pending(&self) -> &str {
+        self.buf.trim_end()
+    }
+
+    /// Feeds one line of input. Returns `None` while parentheses are still
+    /// open, otherwise the printed result of every expression in the buffer.
+    pub fn feed(&mut self, line: &str) -> Option<Vec<Result<String>>> {
+        self.buf.push_str(line);
+        self.buf.push('\n');
+
+        let results = match reader::read(&mut self.arena, &self.buf) {
+            Err(e) if e.is_incomplete() => return None,
+            Err(e) => vec![Err(e.into())],
+            Ok(exps) => exps.iter().map(|exp| self.eval_print(exp)).collect(),
+        };
+        self.buf.clear();
+        self.arena.collect(self.env.roots().iter());
+        Some(results)
+    }
+
+    /// Drops a partially entered expression.
+    pub fn cancel(&mut self) {
+        self.buf.clear();
+    }
+
+    fn eval_print(&mut self, exp: &RispExpRef) -> Result<String> {
+        let result = eval(&mut self.arena, exp, &self.env)?;
+        let s = upgrade(&result)?.borrow().to_string();
+        Ok(s)
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RispError;
+
+    fn feed_ok(repl: &mut Repl, line: &str) -> Vec<String> {
+        repl.feed(line).unwrap().into_iter().map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_multi_line() {
+        let mut repl = Repl::new();
+        assert!(repl.feed("(define (sq x)").is_none());
+        assert!(repl.is_continuing());
+        assert_eq!(repl.pending(), "(define (sq x)");
+        assert!(repl.feed("  (* x x))").is_some());
+        assert!(!repl.is_continuing());
+        assert_eq!(feed_ok(&mut repl, "(sq 3) (sq 4)"), vec!["9", "16"]);
+    }
+
+    #[test]
+    fn test_errors_keep_state() {
+        let mut repl = Repl::new();
+        feed_ok(&mut repl, "(define x 1)");
+
+        let results = repl.feed("y").unwrap();
+        assert_eq!(results, vec![Err(RispError::Unbound("y".to_string()))]);
+
+        let results = repl.feed("x)").unwrap();
+        assert!(matches!(results[..], [Err(RispError::Parse(_))]));
+
+        assert_eq!(feed_ok(&mut repl, "x"), vec!["1"]);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut repl = Repl::new();
+        assert!(repl.feed("(+ 1").is_none());
+        repl.cancel();
+        assert_eq!(feed_ok(&mut repl, "(+ 2 3)"), vec!["5"]);
+    }
+}
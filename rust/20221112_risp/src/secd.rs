@@ -0,0 +1,827 @@
+//! A SECD machine: `compile` turns an expression into a flat instruction
+//! list such as `(ldc 2 ldc 1 add 2 stop)`, and `run` executes it with the
+//! S, E, C and D registers held as arena-allocated lists. Names no `lambda`
+//! binds are looked up with `ldg` in a global environment like `eval`'s.
+//!
+//! Since every register is an immutable list, `call/cc` captures the
+//! continuation by keeping the four registers as they are, and invoking it
+//! any number of times just restores them. A fifth register, W, lists the
+//! `(before . after)` thunks of the active `dynamic-wind` calls, innermost
+//! first.
+
+use std::rc::Rc;
+
+use crate::{
+    arena::{upgrade, Arena, RispExpRef},
+    builtins::{self, check_arity},
+    error::{Result, RispError},
+    env::Env,
+    eval::{expect_symbol, global_env, is_true, list_to_vec, symbol_name},
+    exp::{Builtin, RispAtom, RispExp},
+    symbol::{Keywords, SymbolId},
+};
+
+/// Primitive forms compiled to a single instruction: (source name,
+/// instruction). Each instruction is followed by its argument count, and
+/// the machine runs it through the builtin of the same source name, which
+/// checks the count and argument types just as `eval` does.
+const PRIMITIVES: &[(&str, &str)] = &[
+    ("+", "add"),
+    ("-", "sub"),
+    ("*", "mul"),
+    ("/", "div"),
+    ("eq?", "eq"),
+    ("eqv?", "eqv"),
+    ("=", "numeq"),
+    ("<", "lt"),
+    (">", "gt"),
+    ("<=", "le"),
+    (">=", "ge"),
+    ("car", "car"),
+    ("cdr", "cdr"),
+    ("cons", "cons"),
+    ("atom?", "atom"),
+    ("null?", "null"),
+    ("set-car!", "setcar"),
+    ("set-cdr!", "setcdr"),
+];
+
+/// An entry of `PRIMITIVES` with its names interned, and the builtin its
+/// instruction runs.
+struct Primitive {
+    name: SymbolId,
+    instr: SymbolId,
+    builtin: &'static Builtin,
+}
+
+fn primitives(arena: &mut Arena) -> Rc<[Primitive]> {
+    PRIMITIVES
+        .iter()
+        .map(|(name, instr)| Primitive {
+            name: arena.intern(name),
+            instr: arena.intern(instr),
+            builtin: builtins::lookup(name).expect("every primitive is a builtin"),
+        })
+        .collect()
+}
+
+/// A `lambda` made by `ldf`: its code and the environment it closes over.
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub arity: usize,
+    pub code: RispExpRef,
+    pub env: RispExpRef,
+}
+
+/// A continuation captured by `call/cc`: the registers to restore when it
+/// is invoked.
+#[derive(Debug, Clone)]
+pub struct Continuation {
+    pub s: RispExpRef,
+    pub e: RispExpRef,
+    pub c: RispExpRef,
+    pub d: RispExpRef,
+    pub winders: RispExpRef,
+}
+
+/// Compile-time environment: one name list per frame, innermost first.
+type Names = Vec<Vec<SymbolId>>;
+
+fn locate(names: &Names, name: &SymbolId) -> Option<(usize, usize)> {
+    names.iter().enumerate().find_map(|(i, frame)| {
+        frame.iter().position(|n| n == name).map(|j| (i, j))
+    })
+}
+
+fn param_names(params: &RispExpRef) -> Result<Vec<SymbolId>> {
+    list_to_vec(params)?.iter().map(expect_symbol).collect()
+}
+
+/// Splits `((name exp) ...)` bindings of let/letrec.
+fn bindings(exp: &RispExpRef) -> Result<(Vec<SymbolId>, Vec<RispExpRef>)> {
+    let mut names = Vec::new();
+    let mut exps = Vec::new();
+    for binding in list_to_vec(exp)? {
+        let pair = list_to_vec(&binding)?;
+        check_arity("binding", &pair, 2, Some(2))?;
+        names.push(expect_symbol(&pair[0])?);
+        exps.push(pair[1].clone());
+    }
+    Ok((names, exps))
+}
+
+struct Compiler<'a> {
+    arena: &'a mut Arena,
+    kw: Rc<Keywords>,
+    prims: Rc<[Primitive]>,
+}
+
+impl<'a> Compiler<'a> {
+    fn emit(&mut self, code: &mut Vec<RispExpRef>, instr: &SymbolId) {
+        let instr = self.arena.symbol_cell(instr);
+        code.push(instr);
+    }
+
+    /// Emits `ld (i . j)`.
+    fn emit_ld(&mut self, code: &mut Vec<RispExpRef>, i: usize, j: usize) {
+        let kw = self.kw.clone();
+        let i = self.arena.alloc((i as i64).into());
+        let j = self.arena.alloc((j as i64).into());
+        let loc = self.arena.alloc((i, j).into());
+        self.emit(code, &kw.ld);
+        code.push(loc);
+    }
+
+    /// Emits a primitive instruction taking `count` arguments.
+    fn emit_prim(&mut self, code: &mut Vec<RispExpRef>, instr: &SymbolId, count: usize) {
+        let count = self.arena.alloc((count as i64).into());
+        self.emit(code, instr);
+        code.push(count);
+    }
+
+    /// Compiles `exps` into a list of arguments left on the stack.
+    fn comp_args(&mut self, exps: &[RispExpRef], names: &Names, code: &mut Vec<RispExpRef>) -> Result<()> {
+        let kw = self.kw.clone();
+        let nil = self.arena.nil();
+        self.emit(code, &kw.ldc);
+        code.push(nil);
+        for exp in exps.iter().rev() {
+            self.comp(exp, names, code)?;
+            self.emit_prim(code, &kw.cons, 2);
+        }
+        Ok(())
+    }
+
+    fn comp_body(&mut self, exp: &RispExpRef, names: &Names, last: &SymbolId) -> Result<RispExpRef> {
+        let mut code = Vec::new();
+        self.comp(exp, names, &mut code)?;
+        self.emit(&mut code, last);
+        Ok(self.arena.list(code))
+    }
+
+    /// Compiles `exps` in order, keeping only the last value, as `begin`.
+    fn comp_seq(&mut self, exps: &[RispExpRef], names: &Names, code: &mut Vec<RispExpRef>) -> Result<()> {
+        let kw = self.kw.clone();
+        let Some((last, init)) = exps.split_last() else {
+            let nil = self.arena.nil();
+            self.emit(code, &kw.ldc);
+            code.push(nil);
+            return Ok(());
+        };
+        for exp in init {
+            self.comp(exp, names, code)?;
+            self.emit(code, &kw.pop);
+        }
+        self.comp(last, names, code)
+    }
+
+    /// Emits `ldf` with the argument count and code of a closure.
+    fn emit_ldf(&mut self, code: &mut Vec<RispExpRef>, arity: usize, body: Vec<RispExpRef>) {
+        let kw = self.kw.clone();
+        let arity = self.arena.alloc((arity as i64).into());
+        let body = self.arena.list(body);
+        self.emit(code, &kw.ldf);
+        code.push(arity);
+        code.push(body);
+    }
+
+    /// Emits a call of the thunk at `(0 . j)`.
+    fn emit_call(&mut self, code: &mut Vec<RispExpRef>, j: usize) {
+        let kw = self.kw.clone();
+        let nil = self.arena.nil();
+        self.emit(code, &kw.ldc);
+        code.push(nil);
+        self.emit_ld(code, 0, j);
+        self.emit(code, &kw.ap);
+    }
+
+    fn comp_lambda(&mut self, params: Vec<SymbolId>, body: &[RispExpRef], names: &Names, code: &mut Vec<RispExpRef>) -> Result<()> {
+        let kw = self.kw.clone();
+        let arity = params.len();
+        let mut inner = names.clone();
+        inner.insert(0, params);
+        let mut body_code = Vec::new();
+        self.comp_seq(body, &inner, &mut body_code)?;
+        self.emit(&mut body_code, &kw.rtn);
+        self.emit_ldf(code, arity, body_code);
+        Ok(())
+    }
+
+    fn comp(&mut self, exp: &RispExpRef, names: &Names, code: &mut Vec<RispExpRef>) -> Result<()> {
+        let kw = self.kw.clone();
+        let (head, rest) = match &*upgrade(exp)?.borrow() {
+            RispExp::Atom(RispAtom::Symbol(s)) => {
+                match locate(names, s) {
+                    Some((i, j)) => self.emit_ld(code, i, j),
+                    None => {
+                        let global = self.arena.symbol_cell(s);
+                        self.emit(code, &kw.ldg);
+                        code.push(global);
+                    },
+                }
+                return Ok(());
+            },
+            RispExp::Cons{car, cdr} => (car.clone(), cdr.clone()),
+            _ => {
+                self.emit(code, &kw.ldc);
+                code.push(exp.clone());
+                return Ok(());
+            },
+        };
+
+        let args = list_to_vec(&rest)?;
+        // as in eval, special forms cannot be shadowed, while primitives,
+        // like every other global, can
+        let name = symbol_name(&head)?;
+        let local = name.as_ref().is_some_and(|name| locate(names, name).is_some());
+        match name.as_ref().map(SymbolId::base) {
+            Some(s) if *s == kw.quote => {
+                check_arity("quote", &args, 1, Some(1))?;
+                self.emit(code, &kw.ldc);
+                code.push(args[0].clone());
+            },
+            Some(s) if *s == kw.if_ => {
+                check_arity("if", &args, 2, Some(3))?;
+                self.comp(&args[0], names, code)?;
+                let nil = self.arena.nil();
+                let alt = args.get(2).cloned().unwrap_or(nil);
+                let ct = self.comp_body(&args[1], names, &kw.join)?;
+                let cf = self.comp_body(&alt, names, &kw.join)?;
+                self.emit(code, &kw.sel);
+                code.push(ct);
+                code.push(cf);
+            },
+            Some(s) if *s == kw.begin => self.comp_seq(&args, names, code)?,
+            Some(s) if *s == kw.lambda => {
+                check_arity("lambda", &args, 2, None)?;
+                let params = param_names(&args[0])?;
+                self.comp_lambda(params, &args[1..], names, code)?;
+            },
+            Some(s) if *s == kw.let_ => {
+                check_arity("let", &args, 2, None)?;
+                let (params, exps) = bindings(&args[0])?;
+                self.comp_args(&exps, names, code)?;
+                self.comp_lambda(params, &args[1..], names, code)?;
+                self.emit(code, &kw.ap);
+            },
+            Some(s) if *s == kw.letrec => {
+                check_arity("letrec", &args, 2, None)?;
+                let (params, exps) = bindings(&args[0])?;
+                let mut inner = names.clone();
+                inner.insert(0, params.clone());
+                self.emit(code, &kw.dum);
+                self.comp_args(&exps, &inner, code)?;
+                self.comp_lambda(params, &args[1..], names, code)?;
+                self.emit(code, &kw.rap);
+            },
+            Some(s) if *s == kw.call_cc || *s == kw.call_with_current_continuation => {
+                check_arity("call/cc", &args, 1, Some(1))?;
+                self.comp(&args[0], names, code)?;
+                self.emit(code, &kw.callcc);
+            },
+            Some(s) if *s == kw.dynamic_wind => {
+                check_arity("dynamic-wind", &args, 3, Some(3))?;
+                // applies a closure over (before thunk after) that calls
+                // each in turn, with the winder pushed on W around thunk
+                self.comp_args(&args, names, code)?;
+                let mut body = Vec::new();
+                self.emit_call(&mut body, 0);
+                self.emit(&mut body, &kw.pop);
+                self.emit_ld(&mut body, 0, 2);
+                self.emit_ld(&mut body, 0, 0);
+                self.emit(&mut body, &kw.wind);
+                self.emit_call(&mut body, 1);
+                self.emit(&mut body, &kw.unwind);
+                self.emit_call(&mut body, 2);
+                self.emit(&mut body, &kw.pop);
+                self.emit(&mut body, &kw.rtn);
+                self.emit_ldf(code, 3, body);
+                self.emit(code, &kw.ap);
+            },
+            Some(s) if !local && self.prims.iter().any(|prim| prim.name == *s) => {
+                let prims = self.prims.clone();
+                let prim = prims.iter().find(|prim| prim.name == *s).unwrap();
+                // the first argument ends up on top of the stack
+                for arg in args.iter().rev() {
+                    self.comp(arg, names, code)?;
+                }
+                self.emit_prim(code, &prim.instr, args.len());
+            },
+            _ => {
+                self.comp_args(&args, names, code)?;
+                self.comp(&head, names, code)?;
+                self.emit(code, &kw.ap);
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Compiles a closed expression into SECD code ending in `stop`.
+pub fn compile(arena: &mut Arena, exp: &RispExpRef) -> Result<RispExpRef> {
+    let (kw, prims) = (arena.kw(), primitives(arena));
+    Compiler { arena, kw: kw.clone(), prims }.comp_body(exp, &Vec::new(), &kw.stop)
+}
+
+struct Machine<'a> {
+    arena: &'a mut Arena,
+    s: RispExpRef,
+    e: RispExpRef,
+    c: RispExpRef,
+    d: RispExpRef,
+    w: RispExpRef,
+    kw: Rc<Keywords>,
+    prims: Rc<[Primitive]>,
+    /// Where `ldg` looks up the names no `lambda` binds.
+    globals: Env,
+}
+
+fn pop(reg: &mut RispExpRef) -> Result<RispExpRef> {
+    let (car, cdr) = {
+        let cell = upgrade(reg)?;
+        let cell = cell.borrow();
+        (cell.car_weak()?, cell.cdr_weak()?)
+    };
+    *reg = cdr;
+    Ok(car)
+}
+
+fn nth(list: &RispExpRef, n: i64) -> Result<RispExpRef> {
+    let mut list = list.clone();
+    for _ in 0..n {
+        list = upgrade(&list)?.borrow().cdr_weak()?;
+    }
+    upgrade(&list)?.borrow().car_weak()
+}
+
+/// The cells making up the spine of `list`.
+fn spine(list: &RispExpRef) -> Result<Vec<RispExpRef>> {
+    let mut cells = Vec::new();
+    let mut list = list.clone();
+    while !upgrade(&list)?.borrow().is_nil() {
+        let next = upgrade(&list)?.borrow().cdr_weak()?;
+        cells.push(list);
+        list = next;
+    }
+    Ok(cells)
+}
+
+fn int_of(exp: &RispExpRef) -> Result<i64> {
+    match &*upgrade(exp)?.borrow() {
+        RispExp::Atom(RispAtom::Int(i)) => Ok(*i),
+        exp => Err(RispError::wrong_type("int", exp)),
+    }
+}
+
+/// The argument count operand of `ldf` or a primitive.
+fn count_of(exp: &RispExpRef) -> Result<usize> {
+    match &*upgrade(exp)?.borrow() {
+        exp @ RispExp::Atom(RispAtom::Int(i)) => usize::try_from(*i).map_err(|_| RispError::wrong_type("count", exp)),
+        exp => Err(RispError::wrong_type("count", exp)),
+    }
+}
+
+/// What `ap` can call, copied out of its cell.
+enum Callee {
+    Closure(Closure),
+    Continuation(Continuation),
+    Builtin(Builtin),
+}
+
+impl<'a> Machine<'a> {
+    fn push(&mut self, reg: fn(&mut Self) -> &mut RispExpRef, val: RispExpRef) {
+        let rest = reg(self).clone();
+        let cell = self.arena.alloc((val, rest).into());
+        *reg(self) = cell;
+    }
+
+    fn step(&mut self, instr: &SymbolId) -> Result<Option<RispExpRef>> {
+        let kw = self.kw.clone();
+        match instr {
+            op if *op == kw.stop => return pop(&mut self.s).map(Some),
+            op if *op == kw.ldc => {
+                let x = pop(&mut self.c)?;
+                self.push(|m| &mut m.s, x);
+            },
+            op if *op == kw.ldg => {
+                let name = expect_symbol(&pop(&mut self.c)?)?;
+                let val = self.globals.get(&name).ok_or_else(|| RispError::Unbound(name.to_string()))?;
+                self.push(|m| &mut m.s, val);
+            },
+            op if *op == kw.ld => {
+                let loc = pop(&mut self.c)?;
+                let (i, j) = {
+                    let loc = upgrade(&loc)?;
+                    let loc = loc.borrow();
+                    (int_of(&loc.car_weak()?)?, int_of(&loc.cdr_weak()?)?)
+                };
+                let val = nth(&nth(&self.e, i)?, j)?;
+                self.push(|m| &mut m.s, val);
+            },
+            op if *op == kw.sel => {
+                let ct = pop(&mut self.c)?;
+                let cf = pop(&mut self.c)?;
+                let x = pop(&mut self.s)?;
+                let c = self.c.clone();
+                self.push(|m| &mut m.d, c);
+                self.c = if is_true(&x)? { ct } else { cf };
+            },
+            op if *op == kw.join => self.c = pop(&mut self.d)?,
+            op if *op == kw.ldf => {
+                let arity = count_of(&pop(&mut self.c)?)?;
+                let code = pop(&mut self.c)?;
+                let closure = self.arena.alloc(RispExp::Closure(Closure { arity, code, env: self.e.clone() }));
+                self.push(|m| &mut m.s, closure);
+            },
+            op if *op == kw.ap || *op == kw.rap => {
+                let func = pop(&mut self.s)?;
+                let args = pop(&mut self.s)?;
+                let callee = match &*upgrade(&func)?.borrow() {
+                    RispExp::Closure(closure) => Callee::Closure(closure.clone()),
+                    RispExp::Continuation(k) => Callee::Continuation(k.clone()),
+                    RispExp::Builtin(builtin) => Callee::Builtin(*builtin),
+                    exp => return Err(RispError::wrong_type("procedure", exp)),
+                };
+                let Closure { arity, code, env } = match callee {
+                    Callee::Closure(closure) => closure,
+                    Callee::Continuation(k) => {
+                        self.resume(&func, &k, args)?;
+                        return Ok(None);
+                    },
+                    Callee::Builtin(builtin) => {
+                        let val = (builtin.f)(self.arena, &list_to_vec(&args)?)?;
+                        self.push(|m| &mut m.s, val);
+                        return Ok(None);
+                    },
+                };
+                // the errors eval's bind_params gives
+                let got = list_to_vec(&args)?.len();
+                if got < arity {
+                    return Err(RispError::arity("lambda", got + 1, None, got));
+                }
+                if got > arity {
+                    return Err(RispError::arity("lambda", arity, Some(arity), got));
+                }
+                let (s, e, c) = (self.s.clone(), self.e.clone(), self.c.clone());
+                self.push(|m| &mut m.d, c);
+                if *instr == kw.ap {
+                    self.push(|m| &mut m.d, e);
+                    self.e = self.arena.alloc((args, env).into());
+                } else {
+                    // the closure env is the dummy frame pushed by dum: fill it in
+                    let dummy = upgrade(&env)?;
+                    let outer = dummy.borrow().cdr_weak()?;
+                    self.push(|m| &mut m.d, outer.clone());
+                    dummy.borrow_mut().set_car(args)?;
+                    self.e = env;
+                }
+                self.push(|m| &mut m.d, s);
+                self.s = self.arena.nil();
+                self.c = code;
+            },
+            op if *op == kw.rtn => {
+                let x = pop(&mut self.s)?;
+                self.s = pop(&mut self.d)?;
+                self.e = pop(&mut self.d)?;
+                self.c = pop(&mut self.d)?;
+                self.push(|m| &mut m.s, x);
+            },
+            op if *op == kw.callcc => {
+                let f = pop(&mut self.s)?;
+                let k = self.arena.alloc(RispExp::Continuation(Continuation {
+                    s: self.s.clone(),
+                    e: self.e.clone(),
+                    c: self.c.clone(),
+                    d: self.d.clone(),
+                    winders: self.w.clone(),
+                }));
+                let args = self.arena.list([k]);
+                self.push(|m| &mut m.s, args);
+                self.push(|m| &mut m.s, f);
+                return self.step(&kw.ap);
+            },
+            op if *op == kw.wind => {
+                let before = pop(&mut self.s)?;
+                let after = pop(&mut self.s)?;
+                let winder = self.arena.alloc((before, after).into());
+                self.push(|m| &mut m.w, winder);
+            },
+            op if *op == kw.unwind => {
+                pop(&mut self.w)?;
+            },
+            op if *op == kw.setw => self.w = pop(&mut self.c)?,
+            op if *op == kw.pop => {
+                pop(&mut self.s)?;
+            },
+            op if *op == kw.dum => {
+                let nil = self.arena.nil();
+                self.e = self.arena.alloc((&nil, &self.e).into());
+            },
+            _ => {
+                let prims = self.prims.clone();
+                let prim = prims
+                    .iter()
+                    .find(|prim| prim.instr == *instr)
+                    .ok_or_else(|| RispError::Unbound(instr.to_string()))?;
+                let count = count_of(&pop(&mut self.c)?)?;
+                let args = (0..count).map(|_| pop(&mut self.s)).collect::<Result<Vec<_>>>()?;
+                let val = (prim.builtin.f)(self.arena, &args)?;
+                self.push(|m| &mut m.s, val);
+            },
+        }
+        Ok(None)
+    }
+
+    /// Invokes the continuation `k`, whose handle is `func`, with `args`.
+    /// When W differs from the winders `k` was captured with, this first
+    /// runs code that calls the `after` thunks being left, innermost first,
+    /// and the `before` thunks being entered, outermost first, and then
+    /// invokes `k` again.
+    fn resume(&mut self, func: &RispExpRef, k: &Continuation, args: RispExpRef) -> Result<()> {
+        let kw = self.kw.clone();
+        let vals = list_to_vec(&args)?;
+        check_arity("continuation", &vals, 1, Some(1))?;
+        if self.w.ptr_eq(&k.winders) {
+            self.s = k.s.clone();
+            self.e = k.e.clone();
+            self.c = k.c.clone();
+            self.d = k.d.clone();
+            self.push(|m| &mut m.s, vals[0].clone());
+            return Ok(());
+        }
+
+        // the winder lists share the cells of their common outer part
+        let (leaving, entering) = (spine(&self.w)?, spine(&k.winders)?);
+        let common = leaving.iter().rev().zip(entering.iter().rev()).take_while(|(a, b)| a.ptr_eq(b)).count();
+        let mut code = Vec::new();
+        for cell in &leaving[..leaving.len() - common] {
+            let after = upgrade(&upgrade(cell)?.borrow().car_weak()?)?.borrow().cdr_weak()?;
+            code.push(self.arena.symbol_cell(&kw.unwind));
+            self.call_thunk(&mut code, after);
+        }
+        for cell in entering[..entering.len() - common].iter().rev() {
+            let before = upgrade(&upgrade(cell)?.borrow().car_weak()?)?.borrow().car_weak()?;
+            self.call_thunk(&mut code, before);
+            code.extend([self.arena.symbol_cell(&kw.setw), cell.clone()]);
+        }
+        let (ldc, ap) = (self.arena.symbol_cell(&kw.ldc), self.arena.symbol_cell(&kw.ap));
+        code.extend([ldc.clone(), args, ldc, func.clone(), ap]);
+        self.c = self.arena.list(code);
+        Ok(())
+    }
+
+    /// Appends code calling `thunk` and discarding its value.
+    fn call_thunk(&mut self, code: &mut Vec<RispExpRef>, thunk: RispExpRef) {
+        let kw = self.kw.clone();
+        let (ldc, nil) = (self.arena.symbol_cell(&kw.ldc), self.arena.nil());
+        let (ap, pop) = (self.arena.symbol_cell(&kw.ap), self.arena.symbol_cell(&kw.pop));
+        code.extend([ldc.clone(), nil, ldc, thunk, ap, pop]);
+    }
+}
+
+/// Runs SECD code until `stop` and returns the value on top of the stack.
+pub fn run(arena: &mut Arena, code: &RispExpRef) -> Result<RispExpRef> {
+    let (nil, kw, prims, globals) = (arena.nil(), arena.kw(), primitives(arena), global_env(arena));
+    let mut machine = Machine { arena, s: nil.clone(), e: nil.clone(), c: code.clone(), d: nil.clone(), w: nil, kw, prims, globals };
+    loop {
+        let instr = pop(&mut machine.c)?;
+        if let Some(val) = machine.step(&expect_symbol(&instr)?)? {
+            return Ok(val);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval::{eval, global_env}, reader};
+
+    fn compile_str(src: &str) -> String {
+        let mut arena = Arena::new();
+        let exp = reader::read_one(&mut arena, src).unwrap();
+        let code = compile(&mut arena, &exp).unwrap();
+        let s = upgrade(&code).unwrap().borrow().to_string();
+        s
+    }
+
+    fn run_str(src: &str) -> Result<String> {
+        let mut arena = Arena::new();
+        let exp = reader::read_one(&mut arena, src)?;
+        let code = compile(&mut arena, &exp)?;
+        let result = run(&mut arena, &code)?;
+        let s = upgrade(&result)?.borrow().to_string();
+        Ok(s)
+    }
+
+    fn eval_str(src: &str) -> Result<String> {
+        let mut arena = Arena::new();
+        let env = global_env(&mut arena);
+        let mut result = arena.nil();
+        for exp in reader::read(&mut arena, src)? {
+            result = eval(&mut arena, &exp, &env)?;
+        }
+        let s = upgrade(&result)?.borrow().to_string();
+        Ok(s)
+    }
+
+    #[test]
+    fn test_compile() {
+        assert_eq!(compile_str("'a"), "(ldc a stop)");
+        assert_eq!(compile_str("list"), "(ldg list stop)");
+        assert_eq!(compile_str("(+ 1 2)"), "(ldc 2 ldc 1 add 2 stop)");
+        assert_eq!(compile_str("(- 1)"), "(ldc 1 sub 1 stop)");
+        assert_eq!(compile_str("(if #t 1 2)"), "(ldc #t sel (ldc 1 join) (ldc 2 join) stop)");
+        assert_eq!(
+            compile_str("((lambda (x) x) 1)"),
+            "(ldc nil ldc 1 cons 2 ldf 1 (ld (0 . 0) rtn) ap stop)",
+        );
+        assert_eq!(
+            compile_str("(lambda () 1 2)"),
+            "(ldf 0 (ldc 1 pop ldc 2 rtn) stop)",
+        );
+        assert_eq!(
+            compile_str("(letrec ((f (lambda () (f)))) f)"),
+            "(dum ldc nil ldf 0 (ldc nil ld (1 . 0) ap rtn) cons 2 ldf 1 (ld (0 . 0) rtn) rap stop)",
+        );
+        assert_eq!(compile_str("(call/cc (lambda (k) k))"), "(ldf 1 (ld (0 . 0) rtn) callcc stop)");
+    }
+
+    #[test]
+    fn test_run() {
+        assert_eq!(run_str("'(ldc (quote a))").unwrap(), "(ldc (quote a))");
+        assert_eq!(run_str("(- 10 (* 2 3))").unwrap(), "4");
+        assert_eq!(run_str("(let ((x 1) (y 2)) (cons x y))").unwrap(), "(1 . 2)");
+        assert_eq!(run_str("(car (cdr '(1 2 3)))").unwrap(), "2");
+        assert_eq!(run_str("(null? nil)").unwrap(), "#t");
+    }
+
+    #[test]
+    fn test_letrec() {
+        let src = "
+            (letrec ((fact (lambda (n) (if (= n 0) 1 (* n (fact (- n 1)))))))
+              (fact 10))";
+        assert_eq!(run_str(src).unwrap(), "3628800");
+
+        let src = "
+            (letrec ((even? (lambda (n) (if (= n 0) #t (odd? (- n 1)))))
+                     (odd? (lambda (n) (if (= n 0) #f (even? (- n 1))))))
+              (even? 10))";
+        assert_eq!(run_str(src).unwrap(), "#t");
+
+        assert_eq!(run_str("(letrec ((f (lambda () 1))) (f) (+ (f) 1))").unwrap(), "2");
+    }
+
+    #[test]
+    fn test_matches_eval() {
+        for src in [
+            "42",
+            "'(1 (2 . 3))",
+            "(+ (* 3 4) (- 10 2))",
+            "(if (< 1 2) 'yes 'no)",
+            "(if (> 1 2) 'yes)",
+            "((lambda (x y) (cons y x)) 1 2)",
+            "(((lambda (x) (lambda (y) (- x y))) 10) 3)",
+            "((lambda (f) (f (f 1))) (lambda (x) (* x 5)))",
+            "(cons (atom? 1) (atom? '(1)))",
+            "(eq? 'a 'a)",
+            "(eq? 1 1)",
+            "(eq? 1.0 1.0)",
+            "(eqv? 1.0 1.0)",
+            "(= 1 1.0)",
+            "(= 1 'a)",
+            "(+ 1 2 3)",
+            "(+)",
+            "(- 5)",
+            "(< 1 2 3)",
+            "(< 1 3 2)",
+            "(car)",
+            "((lambda (x) x) 1 2)",
+            "((lambda (x y) x) 1)",
+            "(1 2)",
+            "('f)",
+            "((lambda (x) (+ x 1) (* x 2)) 5)",
+            "(let ((x 1)) (cons x x) (+ x 1))",
+            "(begin 1 2 3)",
+            "(begin)",
+            "(list 1 (+ 1 1) 3)",
+            "(apply + (list 1 2))",
+            "(car (list 'a))",
+            "undefined-name",
+            "((lambda (quote) (quote 1)) 5)",
+            "((lambda (if) (if #t 1 2)) 0)",
+            "(let ((lambda 1)) (lambda (x) x))",
+            "((lambda (+) (+ 1 2)) -)",
+            "((lambda (car) (car 1)) (lambda (x) (cons x x)))",
+        ] {
+            assert_eq!(run_str(src), eval_str(src), "{}", src);
+        }
+
+        assert_eq!(
+            run_str("(letrec ((fact (lambda (n) (if (= n 0) 1 (* n (fact (- n 1))))))) (fact 10))").unwrap(),
+            eval_str("(define (fact n) (if (= n 0) 1 (* n (fact (- n 1))))) (fact 10)").unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_call_cc() {
+        assert_eq!(run_str("(call/cc (lambda (k) k))").unwrap(), "#<continuation>");
+        assert_eq!(run_str("(+ 1 (call/cc (lambda (k) 2)))").unwrap(), "3");
+        assert_eq!(run_str("(+ 1 (call-with-current-continuation (lambda (k) (* 10 (k 2)))))").unwrap(), "3");
+
+        // early exit from a recursive search
+        let src = "
+            (call/cc (lambda (return)
+              (letrec ((find (lambda (lst)
+                               (if (null? lst) #f
+                                   (if (< 2 (car lst)) (return (car lst)) (find (cdr lst)))))))
+                (* 100 (find '(1 2 3 4))))))";
+        assert_eq!(run_str(src).unwrap(), "3");
+
+        // the same continuation invoked again and again
+        let src = "
+            (let ((p (call/cc (lambda (k) (cons 0 k)))))
+              (if (< (car p) 5) ((cdr p) (cons (+ (car p) 1) (cdr p))) (car p)))";
+        assert_eq!(run_str(src).unwrap(), "5");
+
+        assert_eq!(
+            run_str("((call/cc (lambda (k) k)) 1 2)").unwrap_err(),
+            RispError::arity("continuation", 1, Some(1), 2),
+        );
+    }
+
+    #[test]
+    fn test_generator() {
+        // st holds where to resume the walk and where to return its next value
+        let src = "
+            (let ((st (cons #f #f)))
+              (letrec ((yield (lambda (v)
+                                (call/cc (lambda (resume)
+                                  (let ((_ (set-car! st resume))) ((cdr st) v))))))
+                       (walk (lambda (tree)
+                               (if (null? tree) nil
+                                   (if (atom? tree) (yield tree)
+                                       (let ((_ (walk (car tree)))) (walk (cdr tree)))))))
+                       (next (lambda ()
+                               (call/cc (lambda (return)
+                                 (let ((_ (set-cdr! st return)))
+                                   (if (car st)
+                                       ((car st) nil)
+                                       (let ((_ (walk '(1 (2 (3)) 4)))) ((cdr st) 'done)))))))))
+                (let ((a (next)))
+                  (let ((b (next)))
+                    (let ((c (next)))
+                      (let ((d (next)))
+                        (let ((e (next)))
+                          (cons a (cons b (cons c (cons d (cons e nil))))))))))))";
+        assert_eq!(run_str(src).unwrap(), "(1 2 3 4 done)");
+    }
+
+    #[test]
+    fn test_dynamic_wind() {
+        let with_log = |body: &str| format!("
+            (let ((log (cons nil nil)) (st (cons #f nil)))
+              (let ((add (lambda (x) (set-car! log (cons x (car log))))))
+                {}))", body);
+
+        let src = with_log("
+            (let ((r (dynamic-wind (lambda () (add 'in)) (lambda () 'v) (lambda () (add 'out)))))
+              (cons r (car log)))");
+        assert_eq!(run_str(&src).unwrap(), "(v out in)");
+
+        // escaping runs after
+        let src = with_log("
+            (let ((r (call/cc (lambda (k)
+                       (dynamic-wind (lambda () (add 'in)) (lambda () (k 'escaped)) (lambda () (add 'out)))))))
+              (cons r (car log)))");
+        assert_eq!(run_str(&src).unwrap(), "(escaped out in)");
+
+        // re-entering runs before again, and only the winders that change run
+        let src = with_log("
+            (dynamic-wind
+              (lambda () (add 'outer-in))
+              (lambda ()
+                (let ((r (dynamic-wind
+                           (lambda () (add 'in))
+                           (lambda () (call/cc (lambda (k) (let ((_ (set-car! st k))) 'first))))
+                           (lambda () (add 'out)))))
+                  (if (eq? r 'first) ((car st) 'second) (cons r (car log)))))
+              (lambda () (add 'outer-out)))");
+        assert_eq!(run_str(&src).unwrap(), "(second out in out in outer-in)");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(run_str("x").unwrap_err(), RispError::Unbound("x".to_string()));
+        assert_eq!(run_str("(car 1 2)").unwrap_err(), RispError::arity("car", 1, Some(1), 2));
+        assert_eq!(run_str("(car 1)").unwrap_err().to_string(), "wrong type: expected cons, got int 1");
+        assert_eq!(run_str("(/ 1 0)").unwrap_err(), RispError::Arithmetic("division by zero"));
+
+        // hand-written code with a negative count
+        for src in ["(ldf -1 (ldc 1 rtn) stop)", "(ldc 1 add -1 stop)"] {
+            let mut arena = Arena::new();
+            let code = reader::read_one(&mut arena, src).unwrap();
+            let err = run(&mut arena, &code).unwrap_err();
+            assert_eq!(err.to_string(), "wrong type: expected count, got int -1", "{}", src);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, fmt::{Debug, Display}, hash::Hash, rc::Rc};
+
+use crate::env::{Env, WeakEnv};
+
+/// Handle to an interned symbol name. Ids handed out by the same
+/// [`Interner`] are equal exactly when their names are, so comparing or
+/// hashing one only looks at the pointer.
+#[derive(Clone)]
+pub struct SymbolId(Rc<Symbol>);
+
+struct Symbol {
+    name: Rc<str>,
+    // weak, since the symbol may become a key in a frame of this very
+    // environment when the expansion defines it
+    renamed: Option<(SymbolId, WeakEnv)>,
+}
+
+impl SymbolId {
+    pub fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// A fresh symbol with the same name, for an identifier a macro
+    /// template introduces. It equals no other symbol, so the expansion
+    /// cannot capture the caller's variables; where it is not bound, it
+    /// means what this symbol means in `env`, the macro's definition
+    /// environment.
+    pub fn rename(&self, env: &Env) -> SymbolId {
+        SymbolId(Rc::new(Symbol { name: self.0.name.clone(), renamed: Some((self.clone(), env.downgrade())) }))
+    }
+
+    /// The symbol this one was renamed from and the environment it
+    /// resolves in, if that environment is still alive.
+    pub fn renamed(&self) -> Option<(&SymbolId, Option<Env>)> {
+        self.0.renamed.as_ref().map(|(original, env)| (original, env.upgrade()))
+    }
+
+    /// The interned symbol at the end of a chain of renamings.
+    pub fn base(&self) -> &SymbolId {
+        let mut id = self;
+        while let Some((original, _)) = id.renamed() {
+            id = original;
+        }
+        id
+    }
+}
+
+impl PartialEq for SymbolId {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for SymbolId {}
+
+impl Hash for SymbolId {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).cast::<u8>().hash(state)
+    }
+}
+
+impl Debug for SymbolId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.name())
+    }
+}
+
+impl Display for SymbolId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Symbol table mapping each name to its single [`SymbolId`].
+#[derive(Debug, Default)]
+pub struct Interner(HashMap<Rc<str>, SymbolId>);
+
+impl Interner {
+    pub fn intern(&mut self, name: &str) -> SymbolId {
+        if let Some(id) = self.0.get(name) {
+            return id.clone();
+        }
+        let name: Rc<str> = name.into();
+        let id = SymbolId(Rc::new(Symbol { name: name.clone(), renamed: None }));
+        self.0.insert(name, id.clone());
+        id
+    }
+
+    /// The id for `name`, if it has been interned.
+    pub fn lookup(&self, name: &str) -> Option<SymbolId> {
+        self.0.get(name).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+macro_rules! keywords {
+    ($($field:ident = $name:literal,)*) => {
+        /// The symbols `eval`, the SECD compiler and machine, and
+        /// `syntax-rules` dispatch on, interned once per arena so that
+        /// recognizing one is a pointer comparison.
+        #[derive(Debug)]
+        pub struct Keywords {
+            $(pub $field: SymbolId,)*
+        }
+
+        impl Keywords {
+            pub fn new(interner: &mut Interner) -> Self {
+                Keywords { $($field: interner.intern($name),)* }
+            }
+        }
+    };
+}
+
+keywords! {
+    nil = "nil",
+    // special forms
+    quote = "quote",
+    quasiquote = "quasiquote",
+    unquote = "unquote",
+    unquote_splicing = "unquote-splicing",
+    if_ = "if",
+    cond = "cond",
+    else_ = "else",
+    begin = "begin",
+    and = "and",
+    or = "or",
+    define = "define",
+    defmacro = "defmacro",
+    define_syntax = "define-syntax",
+    macroexpand = "macroexpand",
+    macroexpand_1 = "macroexpand-1",
+    lambda = "lambda",
+    let_ = "let",
+    letrec = "letrec",
+    call_cc = "call/cc",
+    call_with_current_continuation = "call-with-current-continuation",
+    dynamic_wind = "dynamic-wind",
+    syntax_rules = "syntax-rules",
+    ellipsis = "...",
+    underscore = "_",
+    // SECD instructions
+    ldc = "ldc",
+    ld = "ld",
+    ldg = "ldg",
+    ldf = "ldf",
+    ap = "ap",
+    rap = "rap",
+    rtn = "rtn",
+    sel = "sel",
+    join = "join",
+    dum = "dum",
+    callcc = "callcc",
+    wind = "wind",
+    unwind = "unwind",
+    setw = "setw",
+    pop = "pop",
+    stop = "stop",
+    cons = "cons",
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::default();
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        assert_eq!(a, interner.intern("a"));
+        assert_ne!(a, b);
+        assert_eq!(a.name(), "a");
+        assert_eq!(interner.lookup("b"), Some(b));
+        assert_eq!(interner.lookup("c"), None);
+        assert_eq!(interner.len(), 2);
+
+        // the same name from another table is a different symbol
+        assert_ne!(a, Interner::default().intern("a"));
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut interner = Interner::default();
+        let a = interner.intern("a");
+        let env = Env::new();
+        let renamed = a.rename(&env);
+        assert_ne!(renamed, a);
+        assert_ne!(renamed, a.rename(&env));
+        assert_eq!(renamed.name(), "a");
+        assert_eq!(renamed.to_string(), "a");
+        assert_eq!(renamed.renamed().unwrap().0, &a);
+        assert_eq!(renamed.rename(&env).base(), &a);
+        assert!(a.renamed().is_none());
+
+        // the renamed symbol does not keep the environment alive
+        drop(env);
+        assert!(renamed.renamed().unwrap().1.is_none());
+    }
+
+    #[test]
+    fn test_keywords() {
+        let mut interner = Interner::default();
+        let kw = Keywords::new(&mut interner);
+        assert_eq!(interner.intern("if"), kw.if_);
+        assert_eq!(kw.ellipsis.name(), "...");
+        assert_ne!(kw.ld, kw.ldc);
+        assert_eq!(kw.quote.rename(&Env::new()).base(), &kw.quote);
+    }
+}
@@ -0,0 +1,386 @@
+//! `syntax-rules` macros. Each rule pairs a pattern, matched against the
+//! macro call, with a template the matched pieces are substituted into.
+//! Identifiers a template introduces are renamed to fresh symbols that
+//! still resolve in the macro's definition environment, so an expansion
+//! neither captures nor is captured by the caller's variables.
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    arena::{upgrade, Arena, RispExpRef},
+    builtins::check_arity,
+    env::Env,
+    equal,
+    error::{Result, RispError},
+    eval::{expect_symbol, list_to_vec, symbol_name},
+    exp::{RispAtom, RispExp},
+    symbol::{Keywords, SymbolId},
+};
+
+/// Library macros written with `syntax-rules`, defined in every global
+/// environment.
+pub(crate) const PRELUDE: &str = "
+(define-syntax let
+  (syntax-rules ()
+    ((_ ((name val) ...) body1 body2 ...)
+     ((lambda (name ...) body1 body2 ...) val ...))))
+
+(define-syntax let*
+  (syntax-rules ()
+    ((_ () body1 body2 ...) (let () body1 body2 ...))
+    ((_ ((name val) rest ...) body1 body2 ...)
+     (let ((name val)) (let* (rest ...) body1 body2 ...)))))
+
+(define-syntax case
+  (syntax-rules (else)
+    ((_ key ((datum ...) body1 body2 ...) ... (else else1 else2 ...))
+     (let ((k key))
+       (cond ((or (eqv? k 'datum) ...) body1 body2 ...) ... (else else1 else2 ...))))
+    ((_ key ((datum ...) body1 body2 ...) ...)
+     (let ((k key))
+       (cond ((or (eqv? k 'datum) ...) body1 body2 ...) ...)))))
+";
+
+/// A macro defined by `(define-syntax name (syntax-rules (literal...) rule...))`.
+#[derive(Debug, Clone)]
+pub struct SyntaxRules {
+    pub literals: Vec<SymbolId>,
+    /// The `(pattern template)` rules, tried in order.
+    pub rules: RispExpRef,
+    pub env: Env,
+    kw: Rc<Keywords>,
+}
+
+/// What a pattern variable matched: one form, or one binding per
+/// repetition of the ellipsis it sits under.
+#[derive(Clone)]
+enum Binding {
+    One(RispExpRef),
+    Many(Vec<Binding>),
+}
+
+type Bindings = HashMap<SymbolId, Binding>;
+
+// symbols hash and compare by pointer, so the environment a renamed symbol
+// carries cannot change its key in these maps
+#[allow(clippy::mutable_key_type)]
+impl SyntaxRules {
+    /// Reads a `(syntax-rules (literal...) rule...)` spec.
+    pub fn new(arena: &Arena, spec: &RispExpRef, env: &Env) -> Result<Self> {
+        let kw = arena.kw();
+        let forms = list_to_vec(spec)?;
+        match forms.first() {
+            Some(head) if symbol_name(head)?.is_some_and(|s| *s.base() == kw.syntax_rules) => (),
+            _ => return Err(RispError::Syntax("expected syntax-rules")),
+        }
+        check_arity("syntax-rules", &forms[1..], 1, None)?;
+        let literals = list_to_vec(&forms[1])?.iter().map(expect_symbol).collect::<Result<_>>()?;
+        let rules = upgrade(spec)?.borrow().cdr()?.borrow().cdr_weak()?;
+        for rule in list_to_vec(&rules)? {
+            check_arity("syntax rule", &list_to_vec(&rule)?, 2, Some(2))?;
+        }
+        Ok(SyntaxRules { literals, rules, env: env.clone(), kw })
+    }
+
+    /// Rewrites the call `form` with the first rule whose pattern matches.
+    pub fn expand(&self, arena: &mut Arena, form: &RispExpRef) -> Result<RispExpRef> {
+        let args = upgrade(form)?.borrow().cdr_weak()?;
+        for rule in list_to_vec(&self.rules)? {
+            let rule = list_to_vec(&rule)?;
+            // the keyword position of a pattern is ignored
+            let pattern = upgrade(&rule[0])?.borrow().cdr_weak()?;
+            let mut bindings = Bindings::new();
+            if self.matches(&pattern, &args, &mut bindings)? {
+                return self.instantiate(arena, &rule[1], &bindings, &mut HashMap::new(), true);
+            }
+        }
+        Err(RispError::Syntax("no syntax rule matches"))
+    }
+
+    fn is_literal(&self, s: &SymbolId) -> bool {
+        self.literals.iter().any(|lit| lit.base() == s.base())
+    }
+
+    fn matches(&self, pattern: &RispExpRef, form: &RispExpRef, bindings: &mut Bindings) -> Result<bool> {
+        let (car, cdr) = match &*upgrade(pattern)?.borrow() {
+            RispExp::Atom(RispAtom::Symbol(s)) if *s.base() == self.kw.underscore => return Ok(true),
+            RispExp::Atom(RispAtom::Symbol(s)) if self.is_literal(s) => {
+                return Ok(symbol_name(form)?.is_some_and(|f| f.base() == s.base()));
+            },
+            RispExp::Atom(RispAtom::Symbol(s)) => {
+                bindings.insert(s.clone(), Binding::One(form.clone()));
+                return Ok(true);
+            },
+            RispExp::Cons{car, cdr} => (car.clone(), cdr.clone()),
+            _ => return equal::equal(pattern, form),
+        };
+
+        let Some(after) = self.ellipsis_rest(&cdr)? else {
+            let Some((form_car, form_cdr)) = as_cons(form)? else { return Ok(false) };
+            return Ok(self.matches(&car, &form_car, bindings)? && self.matches(&cdr, &form_cdr, bindings)?);
+        };
+
+        // `car ...` takes every element the patterns after it leave over
+        let (form_len, after_len) = (cons_len(form)?, cons_len(&after)?);
+        if form_len < after_len {
+            return Ok(false);
+        }
+        let mut repeats = Vec::new();
+        let mut rest = form.clone();
+        for _ in after_len..form_len {
+            let (item, next) = as_cons(&rest)?.expect("counted as a cons");
+            let mut inner = Bindings::new();
+            if !self.matches(&car, &item, &mut inner)? {
+                return Ok(false);
+            }
+            repeats.push(inner);
+            rest = next;
+        }
+        for var in self.pattern_vars(&car)? {
+            let matched = repeats.iter_mut().map(|b| b.remove(&var).expect("bound by the match")).collect();
+            bindings.insert(var, Binding::Many(matched));
+        }
+        self.matches(&after, &rest, bindings)
+    }
+
+    fn pattern_vars(&self, pattern: &RispExpRef) -> Result<Vec<SymbolId>> {
+        let mut vars = Vec::new();
+        symbols_in(pattern, &mut vars)?;
+        vars.retain(|s| *s.base() != self.kw.underscore && *s.base() != self.kw.ellipsis && !self.is_literal(s));
+        Ok(vars)
+    }
+
+    /// Fills in `tmpl`. Symbols that are not pattern variables are renamed,
+    /// consistently within one expansion, except inside `quote`.
+    fn instantiate(
+        &self,
+        arena: &mut Arena,
+        tmpl: &RispExpRef,
+        bindings: &Bindings,
+        renames: &mut HashMap<SymbolId, SymbolId>,
+        rename: bool,
+    ) -> Result<RispExpRef> {
+        let (car, cdr) = match &*upgrade(tmpl)?.borrow() {
+            RispExp::Atom(RispAtom::Symbol(s)) => {
+                return match bindings.get(s) {
+                    Some(Binding::One(val)) => Ok(val.clone()),
+                    Some(Binding::Many(_)) => Err(RispError::Syntax("pattern variable used without ellipsis")),
+                    None if rename => {
+                        let renamed = renames.entry(s.clone()).or_insert_with(|| s.rename(&self.env)).clone();
+                        Ok(arena.alloc(renamed.into()))
+                    },
+                    None => Ok(tmpl.clone()),
+                };
+            },
+            RispExp::Cons{car, cdr} => (car.clone(), cdr.clone()),
+            _ => return Ok(tmpl.clone()),
+        };
+
+        let Some(after) = self.ellipsis_rest(&cdr)? else {
+            let quoted = symbol_name(&car)?.is_some_and(|s| *s.base() == self.kw.quote && !bindings.contains_key(&s));
+            let car = self.instantiate(arena, &car, bindings, renames, rename)?;
+            let cdr = self.instantiate(arena, &cdr, bindings, renames, rename && !quoted)?;
+            return Ok(arena.alloc((car, cdr).into()));
+        };
+
+        // `car ...` repeats once per match of the ellipsis variables in it
+        let mut vars = Vec::new();
+        symbols_in(&car, &mut vars)?;
+        let repeated: Vec<_> = vars.into_iter()
+            .filter_map(|var| match bindings.get(&var) {
+                Some(Binding::Many(matched)) => Some((var, matched)),
+                _ => None,
+            })
+            .collect();
+        let Some((_, first)) = repeated.first() else {
+            return Err(RispError::Syntax("ellipsis without pattern variables"));
+        };
+        let count = first.len();
+        if repeated.iter().any(|(_, matched)| matched.len() != count) {
+            return Err(RispError::Syntax("ellipsis variables matched different lengths"));
+        }
+        let mut items = Vec::with_capacity(count);
+        for i in 0..count {
+            let mut inner = bindings.clone();
+            for (var, matched) in &repeated {
+                inner.insert(var.clone(), matched[i].clone());
+            }
+            items.push(self.instantiate(arena, &car, &inner, renames, rename)?);
+        }
+        let rest = self.instantiate(arena, &after, bindings, renames, rename)?;
+        Ok(items.into_iter().rev().fold(rest, |cdr, car| arena.alloc((car, cdr).into())))
+    }
+
+    /// If `exp` is `(... . rest)`, returns `rest`.
+    fn ellipsis_rest(&self, exp: &RispExpRef) -> Result<Option<RispExpRef>> {
+        match as_cons(exp)? {
+            Some((car, cdr)) if symbol_name(&car)?.is_some_and(|s| *s.base() == self.kw.ellipsis) => Ok(Some(cdr)),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn as_cons(exp: &RispExpRef) -> Result<Option<(RispExpRef, RispExpRef)>> {
+    match &*upgrade(exp)?.borrow() {
+        RispExp::Cons{car, cdr} => Ok(Some((car.clone(), cdr.clone()))),
+        _ => Ok(None),
+    }
+}
+
+/// The number of cons cells before the tail of `exp`.
+fn cons_len(exp: &RispExpRef) -> Result<usize> {
+    let mut len = 0;
+    let mut rest = exp.clone();
+    while let Some((_, next)) = as_cons(&rest)? {
+        len += 1;
+        rest = next;
+    }
+    Ok(len)
+}
+
+fn symbols_in(exp: &RispExpRef, out: &mut Vec<SymbolId>) -> Result<()> {
+    match &*upgrade(exp)?.borrow() {
+        RispExp::Atom(RispAtom::Symbol(s)) if !out.contains(s) => out.push(s.clone()),
+        RispExp::Cons{car, cdr} => {
+            symbols_in(car, out)?;
+            symbols_in(cdr, out)?;
+        },
+        _ => (),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval::{eval, global_env}, reader};
+
+    fn eval_str(src: &str) -> Result<String> {
+        let mut arena = Arena::new();
+        let env = global_env(&mut arena);
+        let mut result = arena.nil();
+        for exp in reader::read(&mut arena, src)? {
+            result = eval(&mut arena, &exp, &env)?;
+        }
+        let s = upgrade(&result)?.borrow().to_string();
+        Ok(s)
+    }
+
+    #[test]
+    fn test_ellipsis() {
+        let src = "
+            (define-syntax my-list (syntax-rules () ((_ x ...) (list x ...))))
+            (list (my-list) (my-list 1 (+ 1 1) 3))";
+        assert_eq!(eval_str(src).unwrap(), "(nil (1 2 3))");
+
+        // nested ellipses and patterns after an ellipsis
+        let src = "
+            (define-syntax pairs (syntax-rules () ((_ (a b ...) ... last) '((b ... a) ... last))))
+            (pairs (1 2 3) (4) (5 6) end)";
+        assert_eq!(eval_str(src).unwrap(), "((2 3 1) (4) (6 5) end)");
+
+        let src = "
+            (define-syntax tail (syntax-rules () ((_ a . rest) 'rest)))
+            (tail 1 2 3)";
+        assert_eq!(eval_str(src).unwrap(), "(2 3)");
+    }
+
+    #[test]
+    fn test_literals() {
+        let src = "
+            (define-syntax my-cond
+              (syntax-rules (else)
+                ((_ (else e ...)) (begin e ...))
+                ((_ (c e ...) clause ...) (if c (begin e ...) (my-cond clause ...)))))
+            (list (my-cond (#f 1) ((= 1 1) 2) (else 3))
+                  (my-cond (#f 1) (else 2 3)))";
+        assert_eq!(eval_str(src).unwrap(), "(2 3)");
+        assert_eq!(
+            eval_str("(define-syntax m (syntax-rules (else) ((_ else) 1))) (m other)").unwrap_err(),
+            RispError::Syntax("no syntax rule matches"),
+        );
+    }
+
+    #[test]
+    fn test_hygiene() {
+        // the template's tmp does not capture the caller's
+        let src = "
+            (define-syntax my-or2
+              (syntax-rules () ((_ a b) (let ((tmp a)) (if tmp tmp b)))))
+            (define tmp 5)
+            (my-or2 #f tmp)";
+        assert_eq!(eval_str(src).unwrap(), "5");
+
+        // the template's car still means the global car inside a local binding
+        let src = "
+            (define-syntax first (syntax-rules () ((_ l) (car l))))
+            (define (f car) (first (list car 2)))
+            (f 1)";
+        assert_eq!(eval_str(src).unwrap(), "1");
+
+        // quoted template symbols are not renamed
+        let src = "
+            (define-syntax sym (syntax-rules () ((_) 'x)))
+            (eq? (sym) 'x)";
+        assert_eq!(eval_str(src).unwrap(), "#t");
+    }
+
+    #[test]
+    fn test_expansion_frame_dropped() {
+        // a macro that defines a template symbol in the frame it was itself
+        // defined in must not tie that frame to its own keys
+        let mut arena = Arena::new();
+        let global = global_env(&mut arena);
+        let frame = global.extend();
+        let src = "
+            (define-syntax def-tmp (syntax-rules () ((_ v) (define tmp v))))
+            (def-tmp 1)";
+        for exp in reader::read(&mut arena, src).unwrap() {
+            eval(&mut arena, &exp, &frame).unwrap();
+        }
+        let weak = frame.downgrade();
+        drop(frame);
+        arena.collect(&global.roots());
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_prelude() {
+        assert_eq!(eval_str("(let ((x 1) (y 2)) (+ x y))").unwrap(), "3");
+        assert_eq!(eval_str("(let () 1 2)").unwrap(), "2");
+        assert_eq!(eval_str("(let* ((x 1) (y (+ x 1))) (list x y))").unwrap(), "(1 2)");
+        let src = "
+            (define (kind x)
+              (case x
+                ((1 2 3) 'small)
+                ((a b) 'letter)
+                (else 'other)))
+            (list (kind 2) (kind 'b) (kind \"s\"))";
+        assert_eq!(eval_str(src).unwrap(), "(small letter other)");
+        assert_eq!(eval_str("(case 9 ((1) 'one))").unwrap(), "nil");
+
+        // the case temporary is invisible to clause bodies
+        assert_eq!(eval_str("(define k 'outer) (case 1 ((1) k))").unwrap(), "outer");
+        assert_eq!(
+            eval_str("(macroexpand '(let ((x 1)) x))").unwrap(),
+            "((lambda (x) x) 1)",
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            eval_str("(define-syntax m (lambda (x) x))").unwrap_err(),
+            RispError::Syntax("expected syntax-rules"),
+        );
+        assert_eq!(
+            eval_str("(define-syntax m (syntax-rules () ((_ x ...) x))) (m 1)").unwrap_err(),
+            RispError::Syntax("pattern variable used without ellipsis"),
+        );
+        assert_eq!(
+            eval_str("(define-syntax m (syntax-rules () ((_ x) (x ...)))) (m 1)").unwrap_err(),
+            RispError::Syntax("ellipsis without pattern variables"),
+        );
+        assert_eq!(eval_str("(define-syntax m (syntax-rules ())) m").unwrap(), "#<syntax>");
+    }
+}
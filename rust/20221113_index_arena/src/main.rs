@@ -0,0 +1,341 @@
+use std::{collections::HashSet, fmt::Display, time::Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExpId(usize);
+
+/// Owns every cell. The first one is the canonical nil, which the symbol
+/// `nil` also allocates as.
+pub struct Arena(Vec<RispExp>);
+
+const NIL: ExpId = ExpId(0);
+
+impl Arena {
+    fn new() -> Self {
+        let mut cells = Vec::with_capacity(100);
+        cells.push(RispExp::Nil);
+        Self(cells)
+    }
+
+    fn alloc(&mut self, exp: RispExp) -> ExpId {
+        if matches!(&exp, RispExp::Nil) || matches!(&exp, RispExp::Atom(RispAtom::Symbol(s)) if s == "nil") {
+            return NIL;
+        }
+        self.0.push(exp);
+        ExpId(self.0.len() - 1)
+    }
+
+    fn nil(&self) -> ExpId {
+        NIL
+    }
+
+    fn get(&self, id: ExpId) -> &RispExp {
+        &self.0[id.0]
+    }
+
+    fn get_mut(&mut self, id: ExpId) -> &mut RispExp {
+        &mut self.0[id.0]
+    }
+
+    fn display(&self, id: ExpId) -> ExpDisplay<'_> {
+        ExpDisplay { arena: self, id }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RispAtom {
+    Int(i64),
+    Symbol(String),
+}
+
+impl Display for RispAtom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RispAtom::Int(i) => write!(f, "{}", i),
+            RispAtom::Symbol(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<i64> for RispAtom {
+    fn from(i: i64) -> Self {
+        RispAtom::Int(i)
+    }
+}
+
+impl From<&str> for RispAtom {
+    fn from(s: &str) -> Self {
+        RispAtom::Symbol(s.to_string())
+    }
+}
+
+#[derive(Debug)]
+pub enum RispExp {
+    /// The empty list, ending every proper list.
+    Nil,
+    Atom(RispAtom),
+    Cons{
+        car: ExpId,
+        cdr: ExpId,
+    },
+}
+
+impl<T> From<T> for RispExp where T: Into<RispAtom> {
+    fn from(t: T) -> Self {
+        RispExp::Atom(t.into())
+    }
+}
+
+impl From<(ExpId, ExpId)> for RispExp {
+    fn from((car, cdr): (ExpId, ExpId)) -> Self {
+        RispExp::Cons{car, cdr}
+    }
+}
+
+impl RispExp {
+    pub fn is_nil(&self) -> bool {
+        matches!(self, RispExp::Nil)
+    }
+}
+
+impl ExpId {
+    pub fn car(self, arena: &Arena) -> anyhow::Result<ExpId> {
+        match arena.get(self) {
+            RispExp::Nil | RispExp::Atom(_) => anyhow::bail!("car of atom"),
+            RispExp::Cons{car, ..} => Ok(*car),
+        }
+    }
+
+    pub fn cdr(self, arena: &Arena) -> anyhow::Result<ExpId> {
+        match arena.get(self) {
+            RispExp::Nil | RispExp::Atom(_) => anyhow::bail!("cdr of atom"),
+            RispExp::Cons{cdr, ..} => Ok(*cdr),
+        }
+    }
+
+    pub fn set_car(self, arena: &mut Arena, val: ExpId) -> anyhow::Result<()> {
+        match arena.get_mut(self) {
+            RispExp::Nil | RispExp::Atom(_) => anyhow::bail!("set-car of atom"),
+            RispExp::Cons{car, ..} => *car = val,
+        }
+        Ok(())
+    }
+
+    pub fn set_cdr(self, arena: &mut Arena, val: ExpId) -> anyhow::Result<()> {
+        match arena.get_mut(self) {
+            RispExp::Nil | RispExp::Atom(_) => anyhow::bail!("set-cdr of atom"),
+            RispExp::Cons{cdr, ..} => *cdr = val,
+        }
+        Ok(())
+    }
+
+    pub fn iter(self, arena: &Arena) -> ExpIdIter<'_> {
+        ExpIdIter { arena, cur: Some(self), visited: HashSet::new() }
+    }
+}
+
+/// Yields the cars of a list, and an error as the last item if the list
+/// ends in anything but nil or runs into a cell it has already read.
+pub struct ExpIdIter<'a> {
+    arena: &'a Arena,
+    cur: Option<ExpId>,
+    visited: HashSet<ExpId>,
+}
+
+impl<'a> Iterator for ExpIdIter<'a> {
+    type Item = anyhow::Result<ExpId>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cur = self.cur.take()?;
+        match self.arena.get(cur) {
+            RispExp::Cons{..} if !self.visited.insert(cur) => Some(Err(anyhow::anyhow!("circular list"))),
+            RispExp::Cons{car, cdr} => {
+                self.cur = Some(*cdr);
+                Some(Ok(*car))
+            },
+            RispExp::Nil => None,
+            RispExp::Atom(a) => Some(Err(anyhow::anyhow!("improper list ending in {}", a))),
+        }
+    }
+}
+
+pub struct ExpDisplay<'a> {
+    arena: &'a Arena,
+    id: ExpId,
+}
+
+impl<'a> Display for ExpDisplay<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.arena.get(self.id) {
+            RispExp::Nil => write!(f, "nil"),
+            RispExp::Atom(a) => write!(f, "{}", a),
+            RispExp::Cons{car, cdr} => {
+                write!(f, "({}", self.arena.display(*car))?;
+                let mut rest = *cdr;
+                loop {
+                    match self.arena.get(rest) {
+                        RispExp::Cons{car, cdr} => {
+                            write!(f, " {}", self.arena.display(*car))?;
+                            rest = *cdr;
+                        },
+                        exp if exp.is_nil() => break,
+                        _ => {
+                            write!(f, " . {}", self.arena.display(rest))?;
+                            break;
+                        },
+                    }
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+const BENCH_LEN: i64 = 1_000_000;
+
+fn bench_index_arena() -> i64 {
+    let mut arena = Arena::new();
+    let mut lst = arena.nil();
+    for i in 0..BENCH_LEN {
+        let v = arena.alloc(i.into());
+        lst = arena.alloc((v, lst).into());
+    }
+
+    lst.iter(&arena)
+        .map(|e| match arena.get(e.unwrap()) {
+            RispExp::Atom(RispAtom::Int(i)) => *i,
+            _ => 0,
+        })
+        .sum()
+}
+
+fn bench_weak_arena() -> i64 {
+    let mut arena = risp::Arena::new();
+    let mut lst = arena.nil();
+    for i in 0..BENCH_LEN {
+        let v = arena.alloc(i.into());
+        lst = arena.alloc((v, lst).into());
+    }
+
+    let lst = lst.upgrade().unwrap();
+    let sum = lst.borrow().iter()
+        .map(|e| match &*risp::upgrade(&e.unwrap()).unwrap().borrow() {
+            risp::RispExp::Atom(risp::RispAtom::Int(i)) => *i,
+            _ => 0,
+        })
+        .sum();
+    sum
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut arena = Arena::new();
+
+    let nil = arena.nil();
+    let v1 = arena.alloc(1.into());
+    let v2 = arena.alloc(2.into());
+    let a = arena.alloc((v2, nil).into());
+    let b = arena.alloc((v1, a).into());
+    println!("b: {}", arena.display(b));
+
+    let x = arena.alloc(42.into());
+    a.set_car(&mut arena, x)?;
+    println!("b: {}", arena.display(b));
+
+    for (name, bench) in [("index", bench_index_arena as fn() -> i64), ("weak", bench_weak_arena)] {
+        let start = Instant::now();
+        let sum = bench();
+        println!("{}: sum={} elapsed={:?}", name, sum, start.elapsed());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_car_cdr() {
+        let mut arena = Arena::new();
+        let v1 = arena.alloc(1.into());
+        let v2 = arena.alloc(2.into());
+        let e = arena.alloc((v1, v2).into());
+
+        assert_eq!(e.car(&arena).unwrap(), v1);
+        assert_eq!(e.cdr(&arena).unwrap(), v2);
+        assert!(v1.car(&arena).is_err());
+        assert!(v1.cdr(&arena).is_err());
+        assert_eq!(arena.display(e).to_string(), "(1 . 2)");
+    }
+
+    #[test]
+    fn test_set_car_cdr() {
+        let mut arena = Arena::new();
+        let nil = arena.nil();
+        let v1 = arena.alloc(5.into());
+        let v2 = arena.alloc(6.into());
+        let v3 = arena.alloc(10.into());
+
+        let a = arena.alloc((v1, nil).into());
+        let b = arena.alloc((v2, a).into());
+        let c = arena.alloc((v3, a).into());
+
+        assert_eq!(arena.display(b).to_string(), "(6 5)");
+        assert_eq!(arena.display(c).to_string(), "(10 5)");
+
+        // shared tail sees the update through both lists
+        let x1 = arena.alloc(9.into());
+        a.set_car(&mut arena, x1).unwrap();
+        assert_eq!(arena.display(b).to_string(), "(6 9)");
+        assert_eq!(arena.display(c).to_string(), "(10 9)");
+
+        let w = arena.alloc(43.into());
+        a.set_cdr(&mut arena, w).unwrap();
+        assert_eq!(arena.display(b).to_string(), "(6 9 . 43)");
+        assert_eq!(arena.display(c).to_string(), "(10 9 . 43)");
+
+        assert!(v1.set_car(&mut arena, x1).is_err());
+        assert!(v1.set_cdr(&mut arena, x1).is_err());
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut arena = Arena::new();
+        let mut lst = arena.nil();
+        for i in (1..=3).rev() {
+            let v = arena.alloc(i.into());
+            lst = arena.alloc((v, lst).into());
+        }
+
+        let values: Vec<_> = lst.iter(&arena).map(|e| arena.display(e.unwrap()).to_string()).collect();
+        assert_eq!(values, vec!["1", "2", "3"]);
+
+        // an improper tail or a cycle ends in an error
+        let tail = lst.cdr(&arena).unwrap().cdr(&arena).unwrap();
+        let v4 = arena.alloc(4.into());
+        tail.set_cdr(&mut arena, v4).unwrap();
+        let items: Vec<_> = lst.iter(&arena).collect();
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[3].as_ref().unwrap_err().to_string(), "improper list ending in 4");
+
+        tail.set_cdr(&mut arena, lst).unwrap();
+        let items: Vec<_> = lst.iter(&arena).collect();
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[3].as_ref().unwrap_err().to_string(), "circular list");
+    }
+
+    #[test]
+    fn test_nil() {
+        let mut arena = Arena::new();
+        let nil = arena.nil();
+        assert_eq!(arena.alloc("nil".into()), nil);
+        assert_eq!(arena.alloc(RispExp::Nil), nil);
+        assert!(arena.get(nil).is_nil());
+        assert_eq!(arena.display(nil).to_string(), "nil");
+        assert!(nil.car(&arena).is_err());
+        assert_eq!(nil.iter(&arena).count(), 0);
+
+        let v = arena.alloc(1.into());
+        let lst = arena.alloc((v, nil).into());
+        assert_eq!(arena.display(lst).to_string(), "(1)");
+    }
+}
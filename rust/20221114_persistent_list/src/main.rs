@@ -0,0 +1,227 @@
+use std::{fmt::{Debug, Display}, rc::Rc};
+
+type Link<T> = Option<Rc<Node<T>>>;
+
+/// An immutable singly-linked list. Nodes are reference counted, so lists
+/// built with `cons` share their tails the way cons cells do.
+struct List<T>(Link<T>);
+
+struct Node<T> {
+    val: T,
+    next: Link<T>,
+}
+
+impl<T> List<T> {
+    fn new() -> Self {
+        List(None)
+    }
+
+    /// A new list with `val` in front of this one, which it shares.
+    fn cons(&self, val: T) -> Self {
+        List(Some(Rc::new(Node { val, next: self.0.clone() })))
+    }
+
+    fn head(&self) -> Option<&T> {
+        self.0.as_ref().map(|node| &node.val)
+    }
+
+    /// Everything after the head, sharing its nodes; empty for an empty list.
+    fn tail(&self) -> Self {
+        List(self.0.as_ref().and_then(|node| node.next.clone()))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    fn iter(&self) -> Iter<'_, T> {
+        Iter(self.0.as_deref())
+    }
+}
+
+impl<T: Clone> List<T> {
+    fn reverse(&self) -> Self {
+        self.iter().fold(List::new(), |acc, val| acc.cons(val.clone()))
+    }
+
+    /// This list followed by `other`. Only this list's nodes are copied;
+    /// the result shares all of `other`.
+    fn append(&self, other: &List<T>) -> Self {
+        self.reverse().iter().fold(other.clone(), |acc, val| acc.cons(val.clone()))
+    }
+}
+
+/// Shares every node.
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
+        List(self.0.clone())
+    }
+}
+
+/// Frees nodes in a loop instead of recursively, stopping at the first
+/// node another list still holds.
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        let mut cur = self.0.take();
+        while let Some(node) = cur {
+            match Rc::try_unwrap(node) {
+                Ok(mut node) => cur = node.next.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let vals: Vec<T> = iter.into_iter().collect();
+        vals.into_iter().rev().fold(List::new(), |acc, val| acc.cons(val))
+    }
+}
+
+impl<T: Display> Display for List<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for val in self {
+            write!(f, "{} -> ", val)?;
+        }
+        write!(f, "None")
+    }
+}
+
+impl<T: Debug> Debug for List<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+// Iter
+struct Iter<'a, T>(Option<&'a Node<T>>);
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.map(|node| {
+            self.0 = node.next.as_deref();
+            &node.val
+        })
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+fn main() {
+    let shared: List<_> = ["c", "d"].into_iter().collect();
+    let a = shared.cons("a");
+    let b = shared.cons("b");
+    println!("{}", a);
+    println!("{}", b);
+    println!("{}", a.append(&b).reverse());
+    if let Some(head) = a.head() {
+        println!("head: {}, tail: {}, empty: {}", head, a.tail(), a.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same_node<T>(a: &List<T>, b: &List<T>) -> bool {
+        match (&a.0, &b.0) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_basics() {
+        let lst = List::new();
+        assert_eq!(lst.head(), None);
+        assert!(lst.tail().is_empty());
+
+        let lst = lst.cons(1).cons(2).cons(3);
+        assert_eq!(lst.head(), Some(&3));
+        let lst = lst.tail();
+        assert_eq!(lst.head(), Some(&2));
+        let lst = lst.tail();
+        assert_eq!(lst.head(), Some(&1));
+        let lst = lst.tail();
+        assert_eq!(lst.head(), None);
+        assert!(lst.is_empty());
+    }
+
+    #[test]
+    fn test_sharing() {
+        let tail: List<_> = ["c".to_string(), "d".to_string()].into_iter().collect();
+        let a = tail.cons("a".to_string());
+        let b = tail.cons("b".to_string());
+        assert!(same_node(&a.tail(), &b.tail()));
+        assert_eq!(a.to_string(), "a -> c -> d -> None");
+        assert_eq!(b.to_string(), "b -> c -> d -> None");
+
+        // dropping one list leaves the shared tail to the other
+        drop(tail);
+        drop(a);
+        assert_eq!(b.to_string(), "b -> c -> d -> None");
+        assert_eq!(Rc::strong_count(b.tail().0.as_ref().unwrap()), 2);
+    }
+
+    #[test]
+    fn test_iter() {
+        let lst: List<_> = (1..=3).collect();
+        let mut iter = lst.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), None);
+
+        // for-in usage (implicit)
+        let mut expected = vec![3, 2, 1];
+        for val in &lst {
+            assert_eq!(*val, expected.pop().unwrap());
+        }
+        assert_eq!(format!("{:?}", lst), "[1, 2, 3]");
+    }
+
+    #[test]
+    fn test_reverse() {
+        let lst: List<_> = (1..=3).collect();
+        assert_eq!(lst.reverse(), (1..=3).rev().collect());
+        assert_eq!(lst, (1..=3).collect());
+        assert!(List::<i32>::new().reverse().is_empty());
+    }
+
+    #[test]
+    fn test_append() {
+        let a: List<_> = (1..=2).collect();
+        let b: List<_> = (3..=4).collect();
+        let ab = a.append(&b);
+        assert_eq!(ab, (1..=4).collect());
+        assert!(same_node(&ab.tail().tail(), &b));
+        assert_eq!(a.append(&List::new()), a);
+        assert!(same_node(&List::new().append(&b), &b));
+    }
+
+    #[test]
+    fn test_drop_long() {
+        let lst: List<_> = (0..1_000_000).collect();
+        let shared = lst.tail().tail();
+        drop(lst);
+        assert_eq!(shared.head(), Some(&2));
+        drop(shared);
+    }
+}